@@ -0,0 +1,229 @@
+use crate::{
+    api::{accounts, balances, orders, positions},
+    request::SANDBOX_BASE_URL,
+    session::Session,
+    streamer::{
+        close_socket, read_socket_message, NotAuthorizedError, NotConnectedError, ReadMessageError,
+        ResponseParseError, Socket,
+    },
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use url::Url;
+
+const ACCOUNT_STREAMER_URL: &str = "wss://streamer.tastyworks.com";
+const SANDBOX_ACCOUNT_STREAMER_URL: &str = "wss://streamer.cert.tastyworks.com";
+
+pub struct Client {
+    url: &'static str,
+    token: String,
+    socket: Option<Socket>,
+    request_id: i32,
+    initial_events: Option<Vec<Event>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ActionResponse {
+    status: String,
+    action: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum Event {
+    Order(OrderUpdate),
+    #[serde(rename = "AccountBalance")]
+    Balance(Box<BalanceUpdate>),
+    #[serde(rename = "CurrentPosition")]
+    Position(PositionUpdate),
+    /// Follows the events of the initial state sent while connecting, returned once by the
+    /// first [`Client::poll_events`] after [`Client::connect`]. Local state built from the
+    /// events before it is warm.
+    #[serde(skip_deserializing)]
+    InitialLoadComplete,
+}
+
+pub type OrderUpdate = orders::Order;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BalanceUpdate {
+    pub account_number: String,
+    #[serde(flatten)]
+    pub balances: balances::Data,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PositionUpdate {
+    pub account_number: String,
+    #[serde(flatten)]
+    pub position: positions::Item,
+}
+
+enum Message {
+    Event(Event),
+    Response(ActionResponse),
+    Other,
+}
+
+// notifications that fail to decode, e.g. of a type added to the API since, are logged and
+// skipped rather than failing the poll
+fn parse_message(msg_json: &str) -> Message {
+    match serde_json::from_str::<Event>(msg_json) {
+        Ok(event) => Message::Event(event),
+        Err(e) => match serde_json::from_str::<ActionResponse>(msg_json) {
+            Ok(response) => Message::Response(response),
+            Err(_) => {
+                log::warn!("Skipping account streamer message: {}", e);
+                Message::Other
+            }
+        },
+    }
+}
+
+impl Client {
+    pub fn new(session: &Session) -> Self {
+        Client {
+            url: if session.base_url == SANDBOX_BASE_URL {
+                SANDBOX_ACCOUNT_STREAMER_URL
+            } else {
+                ACCOUNT_STREAMER_URL
+            },
+            token: session.token.clone(),
+            socket: None,
+            request_id: 0,
+            initial_events: None,
+        }
+    }
+
+    pub fn connect(&mut self, accounts: &[accounts::Account]) -> Result<(), Box<dyn Error>> {
+        log::debug!("Connecting to account streamer");
+        let (socket, response) = tungstenite::connect(Url::parse(self.url)?)?;
+        log::debug!("Connected to account streamer: {}", response.status());
+
+        self.socket = Some(socket);
+        self.initial_events = None;
+        self.send_action(
+            "connect",
+            Some(&format!(
+                "[{}]",
+                accounts
+                    .iter()
+                    .map(|account| format!(r#""{}""#, account.account_number))
+                    .join(",")
+            )),
+        )?;
+
+        let mut initial_events = vec![];
+        loop {
+            let msg = self.read_message(true)?.ok_or(ReadMessageError)?;
+            match parse_message(msg.to_text()?) {
+                // keep notifications that arrive before the connect response for the first poll
+                Message::Event(event) => initial_events.push(event),
+                Message::Response(response) if response.action == "connect" => {
+                    if response.status != "ok" {
+                        return Err(NotAuthorizedError.into());
+                    }
+                    self.initial_events = Some(initial_events);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Closes the connection cleanly, which should be done before dropping the client as
+    /// servers may throttle clients that disconnect abruptly.
+    pub fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.initial_events = None;
+        match self.socket.take() {
+            Some(mut socket) => close_socket(&mut socket),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the events received since the last poll. The first poll after connecting starts
+    /// with the initial state followed by [`Event::InitialLoadComplete`], before any events
+    /// received since.
+    pub fn poll_events(&mut self) -> Result<Vec<Event>, Box<dyn Error>> {
+        if self.socket.is_none() {
+            return Err(NotConnectedError.into());
+        }
+
+        let mut events = vec![];
+        if let Some(mut initial_events) = self.initial_events.take() {
+            events.append(&mut initial_events);
+            events.push(Event::InitialLoadComplete);
+        }
+        while let Some(msg) = self.read_message(false)? {
+            match parse_message(msg.to_text()?) {
+                Message::Event(event) => events.push(event),
+                // the connect response was already handled while connecting
+                Message::Response(response)
+                    if response.action != "connect" && response.status != "ok" =>
+                {
+                    return Err(ResponseParseError(response.action).into());
+                }
+                _ => {}
+            }
+        }
+        self.send_action("heartbeat", None)?;
+
+        Ok(events)
+    }
+
+    fn send_action(&mut self, action: &str, value: Option<&str>) -> Result<(), Box<dyn Error>> {
+        self.request_id += 1;
+        let msg = format!(
+            r#"{{"action":"{action}",{value}"auth-token":"{token}","request-id":{request_id}}}"#,
+            action = action,
+            value = value
+                .map(|value| format!(r#""value":{},"#, value))
+                .unwrap_or_default(),
+            token = self.token,
+            request_id = self.request_id,
+        );
+
+        let socket = self.socket.as_mut().ok_or(NotConnectedError)?;
+        log::debug!("Sending message: {}", action);
+        socket
+            .write_message(tungstenite::Message::Text(msg))
+            .map_err(Into::into)
+    }
+
+    fn read_message(
+        &mut self,
+        blocking: bool,
+    ) -> Result<Option<tungstenite::Message>, Box<dyn Error>> {
+        let socket = self.socket.as_mut().ok_or(NotConnectedError)?;
+        read_socket_message(socket, blocking)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let order = r#"{"type":"Order","data":{"id":1,"account-number":"5WT00000","status":"Live",
+            "order-type":"Notional Market","time-in-force":"GTC Ext"}}"#;
+        assert!(matches!(
+            parse_message(order),
+            Message::Event(Event::Order(_))
+        ));
+        assert!(matches!(
+            parse_message(r#"{"status":"ok","action":"heartbeat"}"#),
+            Message::Response(_)
+        ));
+        assert!(matches!(
+            parse_message(r#"{"type":"Order","data":{"id":"not a number"}}"#),
+            Message::Other
+        ));
+    }
+}
@@ -1,9 +1,12 @@
 use crate::{
     common::{
-        deserialize_integer_or_string_as_decimal, optional_string_serialize, string_serialize,
-        Decimal, ExpirationDate, OptionType,
+        decimal_or_zero_serialize, decimal_serialize, deserialize_integer_or_string_as_decimal,
+        deserialize_optional_integer_or_string_as_decimal, expiration_date_serialize,
+        optional_decimal_serialize, optional_string_serialize, string_serialize, Decimal,
+        ExpirationDate, OptionType,
     },
     csv,
+    errors::{OrderBuildError, ParseEnumError, PositionExportError},
     symbol::OptionSymbol,
 };
 
@@ -13,6 +16,10 @@ use num_traits::{Signed, Zero};
 use serde::{Deserialize, Serialize};
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -38,6 +45,13 @@ pub enum InstrumentType {
     FutureOption,
     Index,
     Cryptocurrency,
+    #[serde(rename = "Equity Offering")]
+    EquityOffering,
+    Warrant,
+    Bond,
+    #[serde(rename = "Currency Pair")]
+    CurrencyPair,
+    #[serde(other)]
     Unknown,
 }
 
@@ -48,6 +62,7 @@ pub mod sessions {
     #[serde(rename_all = "kebab-case")]
     pub(crate) struct Response {
         pub session_token: String,
+        pub remember_token: Option<String>,
     }
 }
 
@@ -68,6 +83,17 @@ pub mod accounts {
     #[serde(rename_all = "kebab-case")]
     pub struct Account {
         pub account_number: String,
+        #[serde(default)]
+        pub is_frozen: bool,
+        #[serde(default)]
+        pub is_closed: bool,
+    }
+
+    impl Account {
+        /// Whether the account can be traded, i.e. it is neither frozen nor closed.
+        pub fn is_active(&self) -> bool {
+            !self.is_frozen && !self.is_closed
+        }
     }
 }
 
@@ -136,7 +162,7 @@ pub mod market_metrics {
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
     pub struct ExpirationImpliedVolatility {
-        #[serde(with = "string_serialize")]
+        #[serde(with = "expiration_date_serialize")]
         pub expiration_date: ExpirationDate,
         #[serde(default, with = "optional_string_serialize")]
         pub implied_volatility: Option<f64>,
@@ -158,6 +184,13 @@ pub mod market_metrics {
         pub expected_report_date: Option<NaiveDate>,
         pub estimated: bool,
         pub time_of_day: Option<EarningsTimeOfDay>,
+        /// Only present once the earnings have been reported.
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub actual_eps: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub consensus_estimate: Option<Decimal>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub updated_at: Option<DateTime<FixedOffset>>,
     }
 
     impl PartialOrd for Earnings {
@@ -187,14 +220,101 @@ pub mod balances {
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
     pub struct Data {
-        #[serde(with = "string_serialize")]
-        cash_balance: Decimal,
-        #[serde(with = "string_serialize")]
-        net_liquidating_value: Decimal,
-        #[serde(with = "string_serialize")]
-        equity_buying_power: Decimal,
-        #[serde(with = "string_serialize")]
-        derivative_buying_power: Decimal,
+        // absent or empty for new accounts
+        #[serde(default, with = "decimal_or_zero_serialize")]
+        pub cash_balance: Decimal,
+        #[serde(default, with = "decimal_or_zero_serialize")]
+        pub net_liquidating_value: Decimal,
+        pub net_liquidating_value_effect: Option<transactions::ValueEffect>,
+        #[serde(default, with = "decimal_or_zero_serialize")]
+        pub equity_buying_power: Decimal,
+        #[serde(default, with = "decimal_or_zero_serialize")]
+        pub derivative_buying_power: Decimal,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub available_trading_funds: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub day_trade_excess: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub pending_cash: Option<Decimal>,
+        pub pending_cash_effect: Option<transactions::ValueEffect>,
+        // the date of the balance snapshot, when the balances are not real-time
+        #[serde(default)]
+        pub snapshot_date: Option<NaiveDate>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub updated_at: Option<DateTime<FixedOffset>>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub futures_margin_requirement: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub futures_overnight_margin_requirement: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub futures_intraday_margin_requirement: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub long_futures_value: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub short_futures_value: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub long_futures_derivative_value: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub short_futures_derivative_value: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub long_cryptocurrency_value: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub short_cryptocurrency_value: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub cryptocurrency_margin_requirement: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub effective_cryptocurrency_buying_power: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub unsettled_cryptocurrency_fiat_amount: Option<Decimal>,
+        pub unsettled_cryptocurrency_fiat_effect: Option<transactions::ValueEffect>,
+    }
+}
+
+pub mod market_data {
+    use super::*;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub(crate) struct Response {
+        pub items: Vec<MarketData>,
+    }
+
+    /// A quote snapshot, independent of whether it came from the REST API or the streamer,
+    /// see [`streamer::Event::quote`](crate::streamer::Event::quote).
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct MarketData {
+        pub symbol: String,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub bid: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub ask: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub bid_size: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub ask_size: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub last: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub mark: Option<Decimal>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub volume: Option<Decimal>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub updated_at: Option<DateTime<FixedOffset>>,
+    }
+
+    impl MarketData {
+        /// The midpoint of the bid and ask, if both are known.
+        pub fn mid(&self) -> Option<Rational64> {
+            Some((self.bid?.0 + self.ask?.0) / 2)
+        }
+    }
+
+    /// The query parameter name for symbols of the given instrument type e.g. `equity-option`.
+    pub(crate) fn instrument_type_parameter(instrument_type: InstrumentType) -> String {
+        serde_json::to_value(instrument_type)
+            .ok()
+            .and_then(|value| value.as_str().map(|s| s.to_lowercase().replace(' ', "-")))
+            .unwrap_or_default()
     }
 }
 
@@ -217,6 +337,43 @@ pub mod positions {
         pub quantity: Rational64,
         pub quantity_direction: QuantityDirection,
         pub instrument_type: InstrumentType,
+        // the notional multiplier, e.g. 100 for equity options or 50 for /ES futures
+        #[serde(
+            default,
+            deserialize_with = "deserialize_optional_integer_or_string_as_decimal",
+            serialize_with = "optional_string_serialize::serialize"
+        )]
+        pub multiplier: Option<Rational64>,
+        /// The market value of the position, only present when requested with
+        /// [`positions_with_marks`](crate::positions_with_marks).
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub mark: Option<Decimal>,
+        /// The price of a single share or contract, see [`Item::mark`].
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub mark_price: Option<Decimal>,
+        /// Only present when requested with
+        /// [`positions_with_marks`](crate::positions_with_marks).
+        #[serde(default)]
+        pub greeks: Option<Greeks>,
+        /// The average price per share or contract the position was opened at.
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub average_open_price: Option<Decimal>,
+    }
+
+    /// The greeks of a position, per share or contract.
+    #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Greeks {
+        #[serde(default, with = "optional_string_serialize")]
+        pub delta: Option<f64>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub gamma: Option<f64>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub theta: Option<f64>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub vega: Option<f64>,
+        #[serde(default, with = "optional_string_serialize")]
+        pub rho: Option<f64>,
     }
 
     #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
@@ -235,17 +392,93 @@ pub mod positions {
         }
     }
 
+    /// An unsigned quantity with its direction. Every `signed_quantity` method in the crate
+    /// follows [`SignedQuantity::value`]: positive when long or bought, negative when short or
+    /// sold.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub struct SignedQuantity {
+        pub quantity: Rational64,
+        pub direction: QuantityDirection,
+    }
+
+    impl SignedQuantity {
+        pub fn new(quantity: Rational64, direction: QuantityDirection) -> Self {
+            Self {
+                quantity: quantity.abs(),
+                direction,
+            }
+        }
+
+        /// Zero is treated as long.
+        pub fn from_value(value: Rational64) -> Self {
+            let direction = if value < Rational64::zero() {
+                QuantityDirection::Short
+            } else {
+                QuantityDirection::Long
+            };
+            Self::new(value, direction)
+        }
+
+        pub fn value(&self) -> Rational64 {
+            match self.direction {
+                QuantityDirection::Long => self.quantity,
+                QuantityDirection::Short => -self.quantity,
+            }
+        }
+    }
+
+    impl fmt::Display for QuantityDirection {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(match self {
+                Self::Long => "Long",
+                Self::Short => "Short",
+            })
+        }
+    }
+
+    /// Parses `Long` or `Short` case-insensitively.
+    impl FromStr for QuantityDirection {
+        type Err = ParseEnumError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.trim().to_ascii_lowercase().as_str() {
+                "long" => Ok(Self::Long),
+                "short" => Ok(Self::Short),
+                _ => Err(ParseEnumError {
+                    type_name: "quantity direction",
+                    value: s.to_string(),
+                }),
+            }
+        }
+    }
+
     impl Item {
+        /// The symbol used to subscribe to quotes for this position. Instruments without a
+        /// known conversion, such as futures options, are returned unchanged; see
+        /// [`positions_quote_symbols`](crate::positions_quote_symbols) for those.
         pub fn quote_symbol(&self) -> String {
-            OptionSymbol::from(&self.symbol).quote_symbol()
+            match self.instrument_type {
+                InstrumentType::EquityOption => OptionSymbol::from(&self.symbol).quote_symbol(),
+                InstrumentType::Future if !self.symbol.starts_with('/') => {
+                    format!("/{}", self.symbol)
+                }
+                InstrumentType::Cryptocurrency => format!("X:{}", self.symbol.replace('/', "")),
+                _ => self.symbol.clone(),
+            }
         }
 
         pub fn expiration_date(&self) -> ExpirationDate {
             OptionSymbol::from(&self.symbol).expiration_date()
         }
 
+        /// The underlying of the position, which for equities and futures is the symbol itself
+        /// and for futures options is the future e.g. `/ESZ3` for `./ESZ3 EW4Z3 231229C4500`.
         pub fn underlying_symbol(&self) -> &str {
-            OptionSymbol::from(&self.symbol).underlying_symbol()
+            let underlying_symbol = OptionSymbol::from(&self.symbol).underlying_symbol();
+            match self.instrument_type {
+                InstrumentType::FutureOption => underlying_symbol.trim_start_matches('.'),
+                _ => underlying_symbol,
+            }
         }
 
         pub fn option_type(&self) -> OptionType {
@@ -257,12 +490,201 @@ pub mod positions {
         }
 
         pub fn signed_quantity(&self) -> Rational64 {
-            self.quantity
-                * match self.quantity_direction {
-                    QuantityDirection::Short => -1,
-                    QuantityDirection::Long => 1,
+            SignedQuantity::new(self.quantity, self.quantity_direction).value()
+        }
+
+        /// The shares per contract of an equity option position, looked up by its root and
+        /// expiration in the option chains of its underlying. Differs from 100 for options
+        /// adjusted for e.g. a split.
+        pub fn shares_per_contract(&self, chains: &[option_chains::Item]) -> Option<i32> {
+            if self.instrument_type != InstrumentType::EquityOption {
+                return None;
+            }
+
+            let option_symbol = OptionSymbol::from(&self.symbol);
+            let root_symbol = option_symbol.root_symbol();
+            let expiration_date = option_symbol.expiration_date();
+            chains
+                .iter()
+                .find(|chain| {
+                    chain.root_symbol == root_symbol && chain.expiration(expiration_date).is_some()
+                })
+                .map(|chain| chain.shares_per_contract)
+        }
+
+        /// The underlying price at expiration at which an equity option position breaks even,
+        /// the strike plus the open price for calls or minus it for puts. This is the same for
+        /// long and short positions, which profit on opposite sides of it. `None` for other
+        /// instrument types or if the open price is unknown.
+        pub fn break_even(&self) -> Option<Rational64> {
+            if self.instrument_type != InstrumentType::EquityOption {
+                return None;
+            }
+
+            let open_price = self.average_open_price?.0;
+            Some(match self.option_type() {
+                OptionType::Call => self.strike_price() + open_price,
+                OptionType::Put => self.strike_price() - open_price,
+            })
+        }
+
+        /// The signed value of the position at the given unit price, or `None` if the
+        /// multiplier is unknown, see [`fill_multipliers`].
+        pub fn notional_value(&self, price: Rational64) -> Option<Rational64> {
+            Some(self.signed_quantity() * price * self.multiplier?)
+        }
+    }
+
+    /// Sets the multiplier of equity option positions to their shares per contract in
+    /// `chains`, e.g. for positions read from a CSV export which have no multiplier.
+    pub fn fill_multipliers(items: &mut [Item], chains: &[option_chains::Item]) {
+        for item in items {
+            if let Some(shares_per_contract) = item.shares_per_contract(chains) {
+                item.multiplier = Some(Rational64::from_integer(shares_per_contract.into()));
+            }
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    pub enum PositionChange {
+        Opened(Item),
+        Closed(Item),
+        QuantityChanged {
+            symbol: String,
+            // signed quantities, negative for short positions
+            old_quantity: Rational64,
+            new_quantity: Rational64,
+        },
+    }
+
+    impl PositionChange {
+        pub fn symbol(&self) -> &str {
+            match self {
+                Self::Opened(item) | Self::Closed(item) => &item.symbol,
+                Self::QuantityChanged { symbol, .. } => symbol,
+            }
+        }
+    }
+
+    /// The changes between two snapshots of positions, ordered by symbol. A position that
+    /// flips from long to short is a quantity change.
+    pub fn diff(old: &[Item], new: &[Item]) -> Vec<PositionChange> {
+        let old_by_symbol: HashMap<&str, &Item> = old
+            .iter()
+            .map(|item| (item.symbol.as_str(), item))
+            .collect();
+        let new_by_symbol: HashMap<&str, &Item> = new
+            .iter()
+            .map(|item| (item.symbol.as_str(), item))
+            .collect();
+
+        let mut changes: Vec<_> = new_by_symbol
+            .iter()
+            .filter_map(|(symbol, new_item)| match old_by_symbol.get(symbol) {
+                None => Some(PositionChange::Opened((*new_item).clone())),
+                Some(old_item) if old_item.signed_quantity() != new_item.signed_quantity() => {
+                    Some(PositionChange::QuantityChanged {
+                        symbol: symbol.to_string(),
+                        old_quantity: old_item.signed_quantity(),
+                        new_quantity: new_item.signed_quantity(),
+                    })
+                }
+                Some(_) => None,
+            })
+            .chain(
+                old_by_symbol
+                    .iter()
+                    .filter(|(symbol, _)| !new_by_symbol.contains_key(*symbol))
+                    .map(|(_, old_item)| PositionChange::Closed((*old_item).clone())),
+            )
+            .collect();
+        changes.sort_by(|a, b| a.symbol().cmp(b.symbol()));
+        changes
+    }
+
+    /// Groups positions by their underlying symbol, so that stock, options and futures options
+    /// positions on the same underlying end up together.
+    pub fn group_by_underlying(items: &[Item]) -> HashMap<String, Vec<&Item>> {
+        let mut groups: HashMap<String, Vec<&Item>> = HashMap::new();
+        for item in items {
+            groups
+                .entry(item.underlying_symbol().to_string())
+                .or_default()
+                .push(item);
+        }
+        groups
+    }
+
+    /// Reconstructs the positions held at the end of `date` by replaying `transactions` up to
+    /// and including that date, where dates are in each transaction's own timezone. Positions
+    /// opened before the earliest transaction are not known, so the transactions should cover
+    /// the whole history of the account. Multipliers are not known and are left as `None`.
+    pub fn as_of(transactions: &[transactions::Item], date: NaiveDate) -> Vec<Item> {
+        use transactions::{Item::*, ReceiveDeliverTransactionSubType};
+
+        let mut transactions: Vec<_> = transactions
+            .iter()
+            .filter(|item| item.executed_at().date_naive() <= date)
+            .collect();
+        transactions.sort();
+
+        let mut quantities: HashMap<&str, (Rational64, InstrumentType)> = HashMap::new();
+        for item in transactions {
+            let (symbol, instrument_type, action, quantity) = match item {
+                Trade(trade) => (
+                    &trade.symbol,
+                    trade.instrument_type,
+                    Some(trade.action),
+                    Some(trade.quantity),
+                ),
+                ReceiveDeliver(receive_deliver) => {
+                    if receive_deliver.effective_action().is_none()
+                        && receive_deliver.transaction_sub_type
+                            == ReceiveDeliverTransactionSubType::Expiration
+                    {
+                        quantities.remove(receive_deliver.symbol.as_str());
+                        continue;
+                    }
+                    (
+                        &receive_deliver.symbol,
+                        receive_deliver.instrument_type,
+                        receive_deliver.effective_action(),
+                        receive_deliver.quantity,
+                    )
                 }
+                MoneyMovement(_) => continue,
+            };
+
+            if let (Some(action), Some(quantity)) = (action, quantity) {
+                let quantity = SignedQuantity::new(quantity.0, action.quantity_direction()).value();
+                let entry = quantities
+                    .entry(symbol)
+                    .or_insert((Rational64::zero(), instrument_type));
+                entry.0 += quantity;
+            }
         }
+
+        let mut items: Vec<_> = quantities
+            .into_iter()
+            .filter(|(_, (quantity, _))| !quantity.is_zero())
+            .map(|(symbol, (quantity, instrument_type))| Item {
+                symbol: symbol.to_string(),
+                quantity: quantity.abs(),
+                quantity_direction: if quantity.is_positive() {
+                    QuantityDirection::Long
+                } else {
+                    QuantityDirection::Short
+                },
+                instrument_type,
+                multiplier: None,
+                mark: None,
+                mark_price: None,
+                greeks: None,
+                average_open_price: None,
+            })
+            .collect();
+        items.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        items
     }
 
     impl From<csv::Position> for Item {
@@ -277,19 +699,85 @@ pub mod positions {
                     "STOCK" => InstrumentType::Equity,
                     _ => unreachable!("Unhandled instrument type: {}", csv.instrument_type),
                 },
+                multiplier: None,
+                mark: None,
+                mark_price: None,
+                greeks: None,
+                average_open_price: None,
             }
         }
     }
+
+    /// Positions from the API have no days open, which is left empty, or net liquidating value,
+    /// which is zero. Exports only have stock and equity option positions with whole quantities,
+    /// so other instrument types and fractional quantities are an error.
+    impl TryFrom<Item> for csv::Position {
+        type Error = PositionExportError;
+
+        fn try_from(item: Item) -> Result<Self, Self::Error> {
+            let (instrument_type, strike_price, call_or_put) = match item.instrument_type {
+                InstrumentType::EquityOption => {
+                    let option_symbol = OptionSymbol::from(&item.symbol);
+                    (
+                        "OPTION",
+                        Decimal(option_symbol.strike_price()),
+                        option_symbol.option_type(),
+                    )
+                }
+                InstrumentType::Equity => ("STOCK", Decimal::default(), OptionType::Call),
+                instrument_type => {
+                    return Err(PositionExportError::UnsupportedInstrumentType(
+                        instrument_type,
+                    ))
+                }
+            };
+            let quantity = item.signed_quantity();
+            let quantity = Some(quantity)
+                .filter(|quantity| quantity.is_integer())
+                .and_then(|quantity| i32::try_from(quantity.to_integer()).ok())
+                .ok_or_else(|| PositionExportError::InvalidQuantity {
+                    symbol: item.symbol.clone(),
+                    quantity,
+                })?;
+
+            Ok(csv::Position {
+                symbol: item.symbol,
+                instrument_type: instrument_type.to_string(),
+                quantity,
+                strike_price,
+                call_or_put,
+                days_open: String::new(),
+                net_liq: Decimal::default(),
+            })
+        }
+    }
 }
 
 pub mod transactions {
     use super::*;
+    use serde::de::value::StrDeserializer;
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub(crate) struct Response {
         pub items: Vec<Item>,
     }
 
+    /// The order of [`transactions_sorted`](crate::transactions_sorted) by execution time.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+    pub enum SortDirection {
+        Asc,
+        Desc,
+    }
+
+    impl SortDirection {
+        pub(crate) fn parameter(&self) -> &'static str {
+            match self {
+                Self::Asc => "Asc",
+                Self::Desc => "Desc",
+            }
+        }
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(tag = "transaction-type")]
     pub enum Item {
@@ -309,13 +797,106 @@ pub mod transactions {
             }
         }
 
-        pub fn executed_at(&mut self) -> DateTime<FixedOffset> {
+        pub fn executed_at(&self) -> DateTime<FixedOffset> {
             match self {
                 Self::Trade(item) => item.executed_at,
                 Self::ReceiveDeliver(item) => item.executed_at,
                 Self::MoneyMovement(item) => item.executed_at,
             }
         }
+
+        pub fn id(&self) -> u32 {
+            match self {
+                Self::Trade(item) => item.id,
+                Self::ReceiveDeliver(item) => item.id,
+                Self::MoneyMovement(item) => item.id,
+            }
+        }
+
+        /// The `transaction-type` of the item as named by the API e.g. `Receive Deliver`.
+        pub fn transaction_type(&self) -> &'static str {
+            match self {
+                Self::Trade(_) => "Trade",
+                Self::ReceiveDeliver(_) => "Receive Deliver",
+                Self::MoneyMovement(_) => "Money Movement",
+            }
+        }
+
+        /// The signed quantity of trades and receive delivers, see
+        /// [`positions::SignedQuantity`]. `None` for money movements.
+        pub fn signed_quantity(&self) -> Option<Rational64> {
+            match self {
+                Self::Trade(item) => Some(item.signed_quantity()),
+                Self::ReceiveDeliver(item) => item.signed_quantity(),
+                Self::MoneyMovement(_) => None,
+            }
+        }
+
+        /// Whether a trade opened a position, see [`TradeAction::opens`]. `None` for other
+        /// transactions.
+        pub fn opens(&self) -> Option<bool> {
+            match self {
+                Self::Trade(item) => Some(item.action.opens()),
+                _ => None,
+            }
+        }
+
+        pub fn value(&self) -> Rational64 {
+            match self {
+                Self::Trade(item) => item.value(),
+                Self::ReceiveDeliver(item) => item.value(),
+                Self::MoneyMovement(item) => item.value(),
+            }
+        }
+    }
+
+    /// The net credit (positive) or debit (negative) of the items e.g. the fills of one order.
+    pub fn net_value(items: &[Item]) -> Rational64 {
+        items.iter().map(Item::value).sum()
+    }
+
+    /// Groups trades by the order they were filled for. Items without an order number are
+    /// skipped.
+    pub fn group_by_order(items: Vec<Item>) -> HashMap<u32, Vec<Item>> {
+        let mut groups: HashMap<u32, Vec<Item>> = HashMap::new();
+        for item in items {
+            if let Item::Trade(Trade {
+                ext_global_order_number: Some(order_number),
+                ..
+            }) = item
+            {
+                groups.entry(order_number).or_default().push(item);
+            }
+        }
+        groups
+    }
+
+    // ordered by execution time, with the id breaking ties
+    impl PartialEq for Item {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+    impl Eq for Item {}
+
+    impl PartialOrd for Item {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Item {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.executed_at()
+                .cmp(&other.executed_at())
+                .then(self.id().cmp(&other.id()))
+        }
+    }
+
+    const STANDARD_SHARES_PER_CONTRACT: i32 = 100;
+
+    pub fn sort_chronological(items: &mut [Item]) {
+        items.sort();
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -328,21 +909,21 @@ pub mod transactions {
         pub executed_at: DateTime<FixedOffset>,
         pub action: TradeAction,
         pub underlying_symbol: String,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         value: Decimal,
         value_effect: ValueEffect,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         pub quantity: Decimal,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         commission: Decimal,
         commission_effect: ValueEffect,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         clearing_fees: Decimal,
         clearing_fees_effect: ValueEffect,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         regulatory_fees: Decimal,
         regulatory_fees_effect: ValueEffect,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         proprietary_index_option_fees: Decimal,
         proprietary_index_option_fees_effect: ValueEffect,
         pub ext_global_order_number: Option<u32>, // not present for crypto trades
@@ -360,6 +941,27 @@ pub mod transactions {
             self.value_effect.apply(self.value.0)
         }
 
+        /// The number of shares traded. For equity options this is the number of contracts
+        /// multiplied by `shares_per_contract`, which should be given for adjusted options
+        /// (see [`option_chains::Item::shares_per_contract`]) and otherwise defaults to 100.
+        /// The quantity of other instruments is returned unchanged.
+        pub fn share_quantity(&self, shares_per_contract: Option<i32>) -> Rational64 {
+            match self.instrument_type {
+                InstrumentType::EquityOption => {
+                    self.quantity.0
+                        * i64::from(shares_per_contract.unwrap_or(STANDARD_SHARES_PER_CONTRACT))
+                }
+                _ => self.quantity.0,
+            }
+        }
+
+        /// The quantity, positive for buys and negative for sells, matching the sign convention
+        /// of [`positions::Item::signed_quantity`].
+        pub fn signed_quantity(&self) -> Rational64 {
+            positions::SignedQuantity::new(self.quantity.0, self.action.quantity_direction())
+                .value()
+        }
+
         pub fn commission(&self) -> Rational64 {
             self.commission_effect.apply(self.commission.0)
         }
@@ -389,6 +991,38 @@ pub mod transactions {
         }
     }
 
+    /// Commissions and fees, negative when paid.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub struct Costs {
+        pub commissions: Rational64,
+        pub fees: Rational64,
+    }
+
+    impl Costs {
+        pub fn total(&self) -> Rational64 {
+            self.commissions + self.fees
+        }
+    }
+
+    /// The commissions and fees of trades and the fees of receive delivers in `items`.
+    pub fn total_costs(items: &[Item]) -> Costs {
+        let zero = Costs {
+            commissions: Rational64::zero(),
+            fees: Rational64::zero(),
+        };
+        items.iter().fold(zero, |mut costs, item| {
+            match item {
+                Item::Trade(trade) => {
+                    costs.commissions += trade.commission();
+                    costs.fees += trade.fees();
+                }
+                Item::ReceiveDeliver(receive_deliver) => costs.fees += receive_deliver.fees(),
+                Item::MoneyMovement(_) => {}
+            }
+            costs
+        })
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
     pub struct ReceiveDeliver {
@@ -399,21 +1033,22 @@ pub mod transactions {
         #[serde(with = "string_serialize")]
         pub executed_at: DateTime<FixedOffset>,
         #[serde(default)]
-        // defined for splits, symbols changes and STO/BTO/STC/BTC transaction sub types
+        // defined for splits, symbols changes and STO/BTO/STC/BTC transaction sub types, absent
+        // for exercises, assignments and expirations (see `effective_action`)
         pub action: Option<TradeAction>,
         pub underlying_symbol: String,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         value: Decimal,
         value_effect: ValueEffect,
-        #[serde(default, with = "optional_string_serialize")]
+        #[serde(default, with = "optional_decimal_serialize")]
         pub quantity: Option<Decimal>,
-        #[serde(default, with = "optional_string_serialize")]
+        #[serde(default, with = "optional_decimal_serialize")]
         clearing_fees: Option<Decimal>,
         clearing_fees_effect: Option<ValueEffect>,
-        #[serde(default, with = "optional_string_serialize")]
+        #[serde(default, with = "optional_decimal_serialize")]
         regulatory_fees: Option<Decimal>,
         regulatory_fees_effect: Option<ValueEffect>,
-        #[serde(default, with = "optional_string_serialize")]
+        #[serde(default, with = "optional_decimal_serialize")]
         proprietary_index_option_fees: Option<Decimal>,
         proprietary_index_option_fees_effect: Option<ValueEffect>,
     }
@@ -462,18 +1097,51 @@ pub mod transactions {
             self.value_effect.apply(self.value.0)
         }
 
+        /// The action if present, otherwise the action inferred from the transaction sub type.
+        /// Assigned options are bought to close and exercised options are sold to close, while
+        /// the delivered underlying is a buy or sell depending on the value effect. Returns `None`
+        /// when no action can be inferred e.g. for expirations, which may close either a long
+        /// or short position.
+        pub fn effective_action(&self) -> Option<TradeAction> {
+            use ReceiveDeliverTransactionSubType::*;
+
+            if self.action.is_some() {
+                return self.action;
+            }
+
+            let is_option = self.is_option();
+            match self.transaction_sub_type {
+                Assignment | CashSettledAssignment if is_option => Some(TradeAction::BuyToClose),
+                Exercise | CashSettledExercise if is_option => Some(TradeAction::SellToClose),
+                Assignment | Exercise => match self.value_effect {
+                    ValueEffect::Debit => Some(TradeAction::Buy),
+                    ValueEffect::Credit => Some(TradeAction::Sell),
+                    ValueEffect::None => None,
+                },
+                _ => None,
+            }
+        }
+
+        /// The signed quantity if the quantity and [`effective_action`](Self::effective_action)
+        /// are known.
+        pub fn signed_quantity(&self) -> Option<Rational64> {
+            let quantity = self.quantity?;
+            let action = self.effective_action()?;
+            Some(positions::SignedQuantity::new(quantity.0, action.quantity_direction()).value())
+        }
+
         pub fn fees(&self) -> Rational64 {
             self.clearing_fees_effect
                 .map(|v| v.apply(self.clearing_fees.unwrap().0))
-                .unwrap_or_else(|| Rational64::zero())
+                .unwrap_or_else(Rational64::zero)
                 + self
                     .regulatory_fees_effect
                     .map(|v| v.apply(self.regulatory_fees.unwrap().0))
-                    .unwrap_or_else(|| Rational64::zero())
+                    .unwrap_or_else(Rational64::zero)
                 + self
                     .proprietary_index_option_fees_effect
                     .map(|v| v.apply(self.proprietary_index_option_fees.unwrap().0))
-                    .unwrap_or_else(|| Rational64::zero())
+                    .unwrap_or_else(Rational64::zero)
         }
 
         pub fn expiration_date(&self) -> ExpirationDate {
@@ -491,19 +1159,189 @@ pub mod transactions {
         pub fn strike_price(&self) -> Rational64 {
             OptionSymbol::from(&self.symbol).strike_price()
         }
+
+        fn is_option(&self) -> bool {
+            matches!(
+                self.instrument_type,
+                InstrumentType::EquityOption | InstrumentType::FutureOption
+            )
+        }
+    }
+
+    /// An assigned or exercised option, with the delivery of the underlying it resulted in.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct AssignmentEvent {
+        pub option: ReceiveDeliver,
+        /// `None` for cash settled options, or if the delivery is not among the items.
+        pub delivery: Option<ReceiveDeliver>,
+    }
+
+    /// Pairs each assigned or exercised option with the delivery of its underlying, which is
+    /// recorded as a separate receive deliver of the same sub type, underlying and day.
+    /// Deliveries of the expected quantity, 100 shares per equity option or one future per
+    /// futures option, are preferred, so that several strikes assigned on the same day are
+    /// paired correctly. Adjusted options fall back to any remaining delivery.
+    pub fn link_assignments(items: &[Item]) -> Vec<AssignmentEvent> {
+        use ReceiveDeliverTransactionSubType::*;
+
+        let receive_delivers: Vec<_> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::ReceiveDeliver(item) => Some(item),
+                _ => None,
+            })
+            .collect();
+        let deliveries: Vec<_> = receive_delivers
+            .iter()
+            .filter(|item| !item.is_option())
+            .filter(|item| matches!(item.transaction_sub_type, Assignment | Exercise))
+            .collect();
+        let options: Vec<_> = receive_delivers
+            .iter()
+            .filter(|item| item.is_option())
+            .filter(|item| {
+                matches!(
+                    item.transaction_sub_type,
+                    Assignment | Exercise | CashSettledAssignment | CashSettledExercise
+                )
+            })
+            .collect();
+
+        let mut linked = vec![None; options.len()];
+        let mut delivered = vec![false; deliveries.len()];
+        for match_quantity in [true, false] {
+            for (option, linked) in options.iter().zip(linked.iter_mut()) {
+                if linked.is_some() {
+                    continue;
+                }
+                let expected_quantity =
+                    option
+                        .quantity
+                        .map(|quantity| match option.instrument_type {
+                            InstrumentType::EquityOption => {
+                                quantity.0 * i64::from(STANDARD_SHARES_PER_CONTRACT)
+                            }
+                            _ => quantity.0,
+                        });
+                *linked = deliveries
+                    .iter()
+                    .zip(&delivered)
+                    .position(|(delivery, delivered)| {
+                        !delivered
+                            && delivery.transaction_sub_type == option.transaction_sub_type
+                            && delivery.underlying_symbol == option.underlying_symbol
+                            && delivery.executed_at.date_naive() == option.executed_at.date_naive()
+                            && (!match_quantity
+                                || (expected_quantity.is_some()
+                                    && delivery.quantity.map(|quantity| quantity.0)
+                                        == expected_quantity))
+                    });
+                if let Some(index) = *linked {
+                    delivered[index] = true;
+                }
+            }
+        }
+
+        options
+            .into_iter()
+            .zip(linked)
+            .map(|(option, index)| AssignmentEvent {
+                option: (*option).clone(),
+                delivery: index.map(|index| (*deliveries[index]).clone()),
+            })
+            .collect()
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
-    #[serde(rename_all = "kebab-case")]
+    #[serde(rename_all = "kebab-case", from = "RawMoneyMovement")]
     pub struct MoneyMovement {
         pub id: u32,
+        #[serde(skip_serializing)]
+        pub transaction_sub_type: Option<MoneyMovementType>,
+        #[serde(rename = "transaction-sub-type")]
+        raw_transaction_sub_type: Option<String>,
         #[serde(with = "string_serialize")]
         pub executed_at: DateTime<FixedOffset>,
+        #[serde(with = "decimal_serialize")]
+        value: Decimal,
+        value_effect: ValueEffect,
+    }
+
+    // keeps the sub type as sent alongside the parsed one
+    #[derive(Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct RawMoneyMovement {
+        id: u32,
+        #[serde(default)]
+        transaction_sub_type: Option<String>,
         #[serde(with = "string_serialize")]
+        executed_at: DateTime<FixedOffset>,
+        #[serde(with = "decimal_serialize")]
         value: Decimal,
         value_effect: ValueEffect,
     }
 
+    impl From<RawMoneyMovement> for MoneyMovement {
+        fn from(raw: RawMoneyMovement) -> Self {
+            let transaction_sub_type = raw.transaction_sub_type.as_deref().map(|sub_type| {
+                MoneyMovementType::deserialize(StrDeserializer::<serde::de::value::Error>::new(
+                    sub_type,
+                ))
+                .unwrap_or(MoneyMovementType::Unknown)
+            });
+            MoneyMovement {
+                id: raw.id,
+                transaction_sub_type,
+                raw_transaction_sub_type: raw.transaction_sub_type,
+                executed_at: raw.executed_at,
+                value: raw.value,
+                value_effect: raw.value_effect,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
+    pub enum MoneyMovementType {
+        Dividend,
+        Interest,
+        Deposit,
+        Withdrawal,
+        #[serde(rename = "Credit Interest")]
+        CreditInterest,
+        #[serde(rename = "Debit Interest")]
+        DebitInterest,
+        Fee,
+        Transfer,
+        #[serde(other)]
+        Unknown,
+    }
+
+    impl MoneyMovementType {
+        // best effort, as CSV exports only describe money movements
+        fn from_description(description: &str) -> Self {
+            let description = description.to_ascii_lowercase();
+            if description.contains("dividend") {
+                Self::Dividend
+            } else if description.contains("interest on credit") {
+                Self::CreditInterest
+            } else if description.contains("interest on debit") {
+                Self::DebitInterest
+            } else if description.contains("interest") {
+                Self::Interest
+            } else if description.contains("deposit") {
+                Self::Deposit
+            } else if description.contains("withdrawal") {
+                Self::Withdrawal
+            } else if description.contains("fee") {
+                Self::Fee
+            } else if description.contains("transfer") {
+                Self::Transfer
+            } else {
+                Self::Unknown
+            }
+        }
+    }
+
     impl PartialEq for MoneyMovement {
         fn eq(&self, other: &Self) -> bool {
             self.id == other.id
@@ -515,6 +1353,13 @@ pub mod transactions {
         pub fn value(&self) -> Rational64 {
             self.value_effect.apply(self.value.0)
         }
+
+        /// The `transaction-sub-type` exactly as sent by the API, which is kept when
+        /// `transaction_sub_type` is [`MoneyMovementType::Unknown`]. `None` for transactions
+        /// read from a CSV export.
+        pub fn raw_transaction_sub_type(&self) -> Option<&str> {
+            self.raw_transaction_sub_type.as_deref()
+        }
     }
 
     #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
@@ -557,6 +1402,25 @@ pub mod transactions {
         pub fn closes(&self) -> bool {
             !self.opens()
         }
+
+        /// The direction the action moves a position in, long for buys and short for sells.
+        pub fn quantity_direction(&self) -> positions::QuantityDirection {
+            match self.value_effect() {
+                ValueEffect::Debit => positions::QuantityDirection::Long,
+                _ => positions::QuantityDirection::Short,
+            }
+        }
+
+        pub fn value_effect(&self) -> ValueEffect {
+            match self {
+                TradeAction::Sell => ValueEffect::Credit,
+                TradeAction::Buy => ValueEffect::Debit,
+                TradeAction::SellToOpen => ValueEffect::Credit,
+                TradeAction::BuyToOpen => ValueEffect::Debit,
+                TradeAction::SellToClose => ValueEffect::Credit,
+                TradeAction::BuyToClose => ValueEffect::Debit,
+            }
+        }
     }
 
     #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
@@ -567,7 +1431,7 @@ pub mod transactions {
     }
 
     impl ValueEffect {
-        fn from_value(value: Rational64) -> Self {
+        pub fn from_value(value: Rational64) -> Self {
             if value.is_positive() {
                 Self::Credit
             } else if value.is_negative() {
@@ -577,13 +1441,18 @@ pub mod transactions {
             }
         }
 
-        fn apply(&self, v: Rational64) -> Rational64 {
+        pub fn apply(&self, v: Rational64) -> Rational64 {
             match self {
                 Self::None => Rational64::zero(),
                 Self::Debit => -v,
                 Self::Credit => v,
             }
         }
+
+        /// Splits a signed value into the unsigned value and effect pair used by the API.
+        pub fn split(value: Decimal) -> (Decimal, Self) {
+            (value.abs(), Self::from_value(value.0))
+        }
     }
 
     impl From<csv::Transaction> for Item {
@@ -602,11 +1471,13 @@ pub mod transactions {
                 });
             let underlying_symbol = csv.underlying_symbol().unwrap_or_default().to_string();
 
-            let split_fees = Decimal(csv.fees.abs().0 / 3);
-            let fees_effect = ValueEffect::from_value(csv.fees.0);
+            let (value, value_effect) = ValueEffect::split(csv.value);
+            let (fees, fees_effect) = ValueEffect::split(csv.fees);
+            let split_fees = Decimal(fees.0 / 3);
 
             if csv.trade_type == "Trade" {
-                let commission = csv.commissions.expect("Missing commissions").abs();
+                let (commission, commission_effect) =
+                    ValueEffect::split(csv.commissions.expect("Missing commissions"));
                 Item::Trade(Trade {
                     id: 0,
                     symbol,
@@ -614,11 +1485,11 @@ pub mod transactions {
                     executed_at: csv.date,
                     action: csv.action.expect("Missing trade action").into(),
                     underlying_symbol,
-                    value: csv.value.abs(),
-                    value_effect: ValueEffect::from_value(csv.value.0),
+                    value,
+                    value_effect,
                     quantity: csv.quantity,
                     commission,
-                    commission_effect: ValueEffect::from_value(commission.0),
+                    commission_effect,
                     clearing_fees: split_fees,
                     clearing_fees_effect: fees_effect,
                     regulatory_fees: split_fees,
@@ -661,8 +1532,8 @@ pub mod transactions {
                     executed_at: csv.date,
                     action: csv.action.map(|action| action.into()),
                     underlying_symbol,
-                    value: csv.value.abs(),
-                    value_effect: ValueEffect::from_value(csv.value.0),
+                    value,
+                    value_effect,
                     quantity: Some(csv.quantity),
                     clearing_fees: Some(split_fees),
                     clearing_fees_effect: Some(fees_effect),
@@ -674,9 +1545,13 @@ pub mod transactions {
             } else if csv.trade_type == "Money Movement" {
                 Item::MoneyMovement(MoneyMovement {
                     id: 0,
+                    transaction_sub_type: Some(MoneyMovementType::from_description(
+                        &csv.description,
+                    )),
+                    raw_transaction_sub_type: None,
                     executed_at: csv.date,
-                    value: csv.value.abs(),
-                    value_effect: ValueEffect::from_value(csv.value.0),
+                    value,
+                    value_effect,
                 })
             } else {
                 unreachable!("Unhandled transaction type: {}", csv.trade_type);
@@ -699,11 +1574,31 @@ pub mod transactions {
 pub mod option_chains {
     use super::*;
 
+    use itertools::Itertools;
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub(crate) struct Response {
         pub items: Vec<Item>,
     }
 
+    /// The nested chain response without strikes, which are skipped while deserializing.
+    #[derive(Clone, Debug, Deserialize)]
+    pub(crate) struct ExpirationsResponse {
+        pub items: Vec<ExpirationsItem>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub(crate) struct ExpirationsItem {
+        pub expirations: Vec<ExpirationDateItem>,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub(crate) struct ExpirationDateItem {
+        #[serde(with = "expiration_date_serialize")]
+        pub expiration_date: ExpirationDate,
+    }
+
     #[derive(Clone, Debug, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
     pub struct Item {
@@ -711,17 +1606,68 @@ pub mod option_chains {
         pub root_symbol: String,
         pub option_chain_type: String,
         pub shares_per_contract: i32,
+        #[serde(default)]
+        pub tick_sizes: Vec<TickSize>,
         pub deliverables: Vec<Deliverable>,
         pub expirations: Vec<Expiration>,
     }
 
-    #[derive(Clone, Debug, Serialize, Deserialize)]
-    #[serde(rename_all = "kebab-case")]
-    pub struct TickSize {
-        #[serde(with = "string_serialize")]
-        pub value: Decimal,
-        #[serde(default, with = "optional_string_serialize")]
-        pub threshold: Option<Decimal>,
+    impl Item {
+        pub fn expiration(&self, expiration_date: ExpirationDate) -> Option<&Expiration> {
+            self.expirations
+                .iter()
+                .find(|expiration| expiration.expiration_date == expiration_date)
+        }
+
+        /// The root to use when building an option symbol for the given expiration, which
+        /// may differ from the underlying symbol e.g. SPXW for SPX weeklies.
+        pub fn root_symbol_for_expiration(&self, expiration_date: ExpirationDate) -> Option<&str> {
+            self.expiration(expiration_date)
+                .map(|_| self.root_symbol.as_str())
+        }
+    }
+
+    /// Finds the root for the given expiration across all chains of an underlying,
+    /// preferring standard chains over adjusted (non-standard) ones.
+    pub fn root_symbol_for_expiration(
+        items: &[Item],
+        expiration_date: ExpirationDate,
+    ) -> Option<&str> {
+        let is_standard = |item: &&Item| item.option_chain_type == "Standard";
+        items
+            .iter()
+            .filter(is_standard)
+            .chain(items.iter().filter(|item| !is_standard(item)))
+            .find_map(|item| item.root_symbol_for_expiration(expiration_date))
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct TickSize {
+        #[serde(with = "decimal_serialize")]
+        pub value: Decimal,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub threshold: Option<Decimal>,
+    }
+
+    /// Rounds `price` to the nearest valid tick. Each tick size applies to prices below its
+    /// threshold, with the last, which has no threshold, applying to all higher prices.
+    /// Prices are returned unchanged if there are no tick sizes.
+    pub fn round_to_tick(price: Decimal, tick_sizes: &[TickSize]) -> Decimal {
+        let tick = tick_sizes
+            .iter()
+            .find(|tick_size| match &tick_size.threshold {
+                Some(threshold) => price.0 < threshold.0,
+                None => true,
+            })
+            .or_else(|| tick_sizes.last())
+            .map(|tick_size| tick_size.value.0)
+            .filter(|tick| !tick.is_zero());
+
+        match tick {
+            Some(tick) => Decimal((price.0 / tick).round() * tick),
+            None => price,
+        }
     }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -731,7 +1677,7 @@ pub mod option_chains {
         pub root_symbol: String,
         pub deliverable_type: String,
         pub description: String,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         pub amount: Decimal,
         pub instrument_type: Option<InstrumentType>,
         #[serde(with = "string_serialize")]
@@ -742,7 +1688,7 @@ pub mod option_chains {
     #[serde(rename_all = "kebab-case")]
     pub struct Expiration {
         pub expiration_type: ExpirationType,
-        #[serde(with = "string_serialize")]
+        #[serde(with = "expiration_date_serialize")]
         pub expiration_date: ExpirationDate,
         pub days_to_expiration: i32,
         pub settlement_type: String,
@@ -750,19 +1696,1485 @@ pub mod option_chains {
         pub strikes: Vec<ExpirationStrike>,
     }
 
+    impl Expiration {
+        /// Days until expiration from the given date, which unlike `days_to_expiration` does
+        /// not go stale when the chain is cached.
+        pub fn days_to_expiration_from(&self, today: NaiveDate) -> i64 {
+            (self.expiration_date.0 - today).num_days()
+        }
+
+        /// The sorted, deduplicated strike prices of the expiration.
+        pub fn strike_prices(&self) -> Vec<Rational64> {
+            self.strikes
+                .iter()
+                .map(|strike| strike.strike_price.0)
+                .sorted()
+                .dedup()
+                .collect()
+        }
+
+        /// Whether the options settle on the opening price e.g. monthly SPX, as opposed to
+        /// the closing price of the expiration date e.g. SPXW.
+        pub fn is_am_settled(&self) -> bool {
+            self.settlement_type.eq_ignore_ascii_case("AM")
+        }
+
+        /// Whether this is an end of month expiration, which for indices are PM settled
+        /// unlike the AM settled regular monthly expiration. Applies to any underlying.
+        pub fn is_end_of_month(&self) -> bool {
+            self.expiration_type == ExpirationType::EndOfMonth
+        }
+    }
+
     #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
     pub enum ExpirationType {
         Regular,
         Weekly,
         Quarterly,
+        #[serde(rename = "End-Of-Month")]
+        EndOfMonth,
     }
 
-    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
     pub struct ExpirationStrike {
-        #[serde(with = "string_serialize")]
+        #[serde(with = "decimal_serialize")]
         pub strike_price: Decimal,
         pub call: String,
         pub put: String,
     }
+
+    // ordered by strike price, with the symbols breaking ties
+    impl PartialOrd for ExpirationStrike {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for ExpirationStrike {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.strike_price
+                .0
+                .cmp(&other.strike_price.0)
+                .then_with(|| self.call.cmp(&other.call))
+                .then_with(|| self.put.cmp(&other.put))
+        }
+    }
+}
+
+pub mod futures_option_chains {
+    use super::{option_chains::ExpirationType, *};
+
+    use itertools::Itertools;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub(crate) struct Response {
+        #[serde(default)]
+        pub futures: Vec<Future>,
+        pub option_chains: Vec<Item>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Future {
+        pub symbol: String,
+        pub root_symbol: String,
+        pub expiration_date: NaiveDate,
+        pub days_to_expiration: i32,
+        #[serde(default)]
+        pub active_month: bool,
+        #[serde(default)]
+        pub next_active_month: bool,
+    }
+
+    /// The option chain of a futures product e.g. `/ES`, whose expirations span the option
+    /// roots of each contract month e.g. `EW4` for the end of month options on `/ESZ3`.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Item {
+        pub underlying_symbol: String,
+        pub root_symbol: String,
+        pub exercise_style: Option<String>,
+        pub expirations: Vec<Expiration>,
+    }
+
+    impl Item {
+        /// The distinct option roots of the chain, in order of expiration.
+        pub fn option_roots(&self) -> Vec<&str> {
+            self.expirations
+                .iter()
+                .sorted_by_key(|expiration| expiration.expiration_date)
+                .map(|expiration| expiration.option_root_symbol.as_str())
+                .unique()
+                .collect()
+        }
+
+        /// The dxfeed streamer symbol of a futures option e.g. `./EW4X23P4500:XCME`,
+        /// which unlike equity options cannot be derived from the symbol alone.
+        pub fn streamer_symbol(&self, symbol: &str) -> Option<&str> {
+            self.expirations
+                .iter()
+                .flat_map(|expiration| &expiration.strikes)
+                .find_map(|strike| {
+                    if strike.call == symbol {
+                        strike.call_streamer_symbol.as_deref()
+                    } else if strike.put == symbol {
+                        strike.put_streamer_symbol.as_deref()
+                    } else {
+                        None
+                    }
+                })
+        }
+
+        /// The expirations of the given option root e.g. `EW` for end of month options.
+        pub fn expirations_for_root<'a>(
+            &'a self,
+            option_root_symbol: &'a str,
+        ) -> impl Iterator<Item = &'a Expiration> + 'a {
+            self.expirations
+                .iter()
+                .filter(move |expiration| expiration.option_root_symbol == option_root_symbol)
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Expiration {
+        // the future the options are on e.g. /ESZ3
+        pub underlying_symbol: String,
+        pub root_symbol: String,
+        pub option_root_symbol: String,
+        pub option_contract_symbol: Option<String>,
+        pub expiration_date: NaiveDate,
+        pub days_to_expiration: i32,
+        pub expiration_type: Option<ExpirationType>,
+        pub settlement_type: Option<String>,
+        #[serde(default)]
+        pub strikes: Vec<Strike>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Strike {
+        #[serde(with = "decimal_serialize")]
+        pub strike_price: Decimal,
+        pub call: String,
+        pub call_streamer_symbol: Option<String>,
+        pub put: String,
+        pub put_streamer_symbol: Option<String>,
+    }
+}
+
+pub mod instruments {
+    use super::*;
+
+    use itertools::Itertools;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub(crate) struct FuturesResponse {
+        pub items: Vec<Future>,
+    }
+
+    /// A futures contract e.g. `/ESZ3`.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Future {
+        pub symbol: String,
+        pub product_code: String,
+        pub streamer_symbol: Option<String>,
+        pub expiration_date: NaiveDate,
+        #[serde(default)]
+        pub active: bool,
+        #[serde(default)]
+        pub active_month: bool,
+        #[serde(default)]
+        pub next_active_month: bool,
+        #[serde(default)]
+        pub is_closing_only: bool,
+    }
+
+    /// A futures product e.g. `ES`, with its contracts in order of expiration.
+    #[derive(Clone, Debug)]
+    pub struct FutureProduct {
+        pub product_code: String,
+        pub contracts: Vec<Future>,
+    }
+
+    impl FutureProduct {
+        /// The contract the exchange considers the active month, falling back to the first
+        /// active contract to expire.
+        pub fn front_month(&self) -> Option<&Future> {
+            self.contracts
+                .iter()
+                .find(|future| future.active_month)
+                .or_else(|| self.contracts.iter().find(|future| future.active))
+        }
+
+        pub fn next_active_month(&self) -> Option<&Future> {
+            self.contracts
+                .iter()
+                .find(|future| future.next_active_month)
+        }
+
+        /// The symbols of the active contracts.
+        pub fn active_symbols(&self) -> Vec<&str> {
+            self.contracts
+                .iter()
+                .filter(|future| future.active)
+                .map(|future| future.symbol.as_str())
+                .collect()
+        }
+    }
+
+    /// Groups contracts into products ordered by product code.
+    pub(crate) fn group_by_product(futures: Vec<Future>) -> Vec<FutureProduct> {
+        futures
+            .into_iter()
+            .into_group_map_by(|future| future.product_code.clone())
+            .into_iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(product_code, mut contracts)| {
+                contracts.sort_by_key(|future| future.expiration_date);
+                FutureProduct {
+                    product_code,
+                    contracts,
+                }
+            })
+            .collect()
+    }
+}
+
+pub mod orders {
+    use super::{
+        transactions::{TradeAction, ValueEffect},
+        *,
+    };
+
+    use std::collections::HashSet;
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
+    pub enum OrderType {
+        Limit,
+        Market,
+        Stop,
+        #[serde(rename = "Stop Limit")]
+        StopLimit,
+        #[serde(rename = "Notional Market")]
+        NotionalMarket,
+        /// An order type added to the API since this version, which can be read but not sent.
+        #[serde(other)]
+        Unknown,
+    }
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
+    #[serde(try_from = "TimeInForceFields", into = "TimeInForceFields")]
+    pub enum TimeInForce {
+        Day,
+        GTC,
+        GTD(NaiveDate),
+        Ext,
+        GTCExt,
+        IOC,
+        /// A time in force added to the API since this version, which can be read but not sent:
+        /// [`OrderBuilder::build`] rejects it.
+        Unknown,
+    }
+
+    // the expiry of a GTD order is sent as a separate field alongside the time in force
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct TimeInForceFields {
+        time_in_force: TimeInForceKind,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gtc_date: Option<NaiveDate>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum TimeInForceKind {
+        Day,
+        #[serde(rename = "GTC")]
+        Gtc,
+        #[serde(rename = "GTD")]
+        Gtd,
+        Ext,
+        #[serde(rename = "GTC Ext")]
+        GtcExt,
+        #[serde(rename = "IOC")]
+        Ioc,
+        #[serde(other)]
+        Unknown,
+    }
+
+    impl From<TimeInForce> for TimeInForceFields {
+        fn from(time_in_force: TimeInForce) -> Self {
+            let (time_in_force, gtc_date) = match time_in_force {
+                TimeInForce::Day => (TimeInForceKind::Day, None),
+                TimeInForce::GTC => (TimeInForceKind::Gtc, None),
+                TimeInForce::GTD(date) => (TimeInForceKind::Gtd, Some(date)),
+                TimeInForce::Ext => (TimeInForceKind::Ext, None),
+                TimeInForce::GTCExt => (TimeInForceKind::GtcExt, None),
+                TimeInForce::IOC => (TimeInForceKind::Ioc, None),
+                TimeInForce::Unknown => (TimeInForceKind::Unknown, None),
+            };
+            Self {
+                time_in_force,
+                gtc_date,
+            }
+        }
+    }
+
+    impl TryFrom<TimeInForceFields> for TimeInForce {
+        type Error = &'static str;
+
+        fn try_from(fields: TimeInForceFields) -> Result<Self, Self::Error> {
+            Ok(match fields.time_in_force {
+                TimeInForceKind::Day => TimeInForce::Day,
+                TimeInForceKind::Gtc => TimeInForce::GTC,
+                TimeInForceKind::Gtd => {
+                    TimeInForce::GTD(fields.gtc_date.ok_or("Missing gtc-date for GTD order")?)
+                }
+                TimeInForceKind::Ext => TimeInForce::Ext,
+                TimeInForceKind::GtcExt => TimeInForce::GTCExt,
+                TimeInForceKind::Ioc => TimeInForce::IOC,
+                TimeInForceKind::Unknown => TimeInForce::Unknown,
+            })
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct NewOrder {
+        #[serde(flatten)]
+        pub time_in_force: TimeInForce,
+        pub order_type: OrderType,
+        #[serde(
+            default,
+            with = "optional_decimal_serialize",
+            skip_serializing_if = "Option::is_none"
+        )]
+        pub price: Option<Decimal>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub price_effect: Option<ValueEffect>,
+        pub legs: Vec<Leg>,
+    }
+
+    impl NewOrder {
+        /// The limit price as a credit (positive) or debit (negative), see [`signed_price`].
+        pub fn signed_price(&self) -> Option<Rational64> {
+            signed_price(self.price, self.price_effect)
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct OrderResponse {
+        pub order: Order,
+        #[serde(default)]
+        pub warnings: Vec<Warning>,
+    }
+
+    /// A warning about an accepted order e.g. that it is outside regular trading hours.
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Warning {
+        pub code: String,
+        pub message: String,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Order {
+        pub id: u64,
+        pub account_number: String,
+        pub status: String,
+        pub order_type: OrderType,
+        #[serde(flatten)]
+        pub time_in_force: TimeInForce,
+        pub underlying_symbol: Option<String>,
+        #[serde(default, with = "optional_decimal_serialize")]
+        pub price: Option<Decimal>,
+        pub price_effect: Option<ValueEffect>,
+        #[serde(default)]
+        pub legs: Vec<Leg>,
+        /// Why the order was rejected, if its status is `Rejected`.
+        pub reject_reason: Option<String>,
+        pub complex_order_tag: Option<String>,
+    }
+
+    impl Order {
+        /// The limit price as a credit (positive) or debit (negative), see [`signed_price`].
+        pub fn signed_price(&self) -> Option<Rational64> {
+            signed_price(self.price, self.price_effect)
+        }
+    }
+
+    /// Combines an unsigned order price with its `price-effect`, which is a [`ValueEffect`] like
+    /// the value of a transaction. `None` without a price, or with a price but no effect.
+    pub fn signed_price(
+        price: Option<Decimal>,
+        price_effect: Option<ValueEffect>,
+    ) -> Option<Rational64> {
+        Some(price_effect?.apply(price?.0))
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub struct Leg {
+        pub instrument_type: InstrumentType,
+        pub symbol: String,
+        #[serde(
+            deserialize_with = "deserialize_integer_or_string_as_decimal",
+            serialize_with = "string_serialize::serialize"
+        )]
+        pub quantity: Rational64,
+        pub action: TradeAction,
+    }
+
+    impl Leg {
+        pub fn signed_quantity(&self) -> Rational64 {
+            positions::SignedQuantity::new(self.quantity, self.action.quantity_direction()).value()
+        }
+
+        pub fn new(symbol: impl Into<String>, quantity: i64, action: TradeAction) -> Self {
+            let symbol = symbol.into();
+            Self {
+                instrument_type: instrument_type_for_symbol(&symbol),
+                symbol,
+                quantity: Rational64::from_integer(quantity),
+                action,
+            }
+        }
+    }
+
+    fn instrument_type_for_symbol(symbol: &str) -> InstrumentType {
+        if symbol.starts_with("./") {
+            InstrumentType::FutureOption
+        } else if symbol.starts_with('/') {
+            InstrumentType::Future
+        } else if symbol.split_whitespace().nth(1).is_some() {
+            InstrumentType::EquityOption
+        } else {
+            InstrumentType::Equity
+        }
+    }
+
+    /// Assembles a [`NewOrder`], checking that the legs and price are consistent on
+    /// [`OrderBuilder::build`]. Orders default to a day limit order.
+    #[derive(Clone, Debug)]
+    pub struct OrderBuilder {
+        time_in_force: TimeInForce,
+        order_type: OrderType,
+        price: Option<(Decimal, ValueEffect)>,
+        legs: Vec<Leg>,
+    }
+
+    impl Default for OrderBuilder {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl OrderBuilder {
+        pub fn new() -> Self {
+            Self {
+                time_in_force: TimeInForce::Day,
+                order_type: OrderType::Limit,
+                price: None,
+                legs: vec![],
+            }
+        }
+
+        pub fn leg(mut self, leg: Leg) -> Self {
+            self.legs.push(leg);
+            self
+        }
+
+        pub fn buy_to_open(self, symbol: impl Into<String>, quantity: i64) -> Self {
+            self.leg(Leg::new(symbol, quantity, TradeAction::BuyToOpen))
+        }
+
+        pub fn sell_to_open(self, symbol: impl Into<String>, quantity: i64) -> Self {
+            self.leg(Leg::new(symbol, quantity, TradeAction::SellToOpen))
+        }
+
+        pub fn buy_to_close(self, symbol: impl Into<String>, quantity: i64) -> Self {
+            self.leg(Leg::new(symbol, quantity, TradeAction::BuyToClose))
+        }
+
+        pub fn sell_to_close(self, symbol: impl Into<String>, quantity: i64) -> Self {
+            self.leg(Leg::new(symbol, quantity, TradeAction::SellToClose))
+        }
+
+        pub fn limit_price(mut self, price: Decimal, price_effect: ValueEffect) -> Self {
+            self.order_type = OrderType::Limit;
+            self.price = Some((price, price_effect));
+            self
+        }
+
+        pub fn market(mut self) -> Self {
+            self.order_type = OrderType::Market;
+            self.price = None;
+            self
+        }
+
+        pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+            self.time_in_force = time_in_force;
+            self
+        }
+
+        pub fn build(self) -> Result<NewOrder, OrderBuildError> {
+            if self.legs.is_empty() {
+                return Err(OrderBuildError::NoLegs);
+            }
+            // unknown variants are only read from the API, which would reject them
+            if self.time_in_force == TimeInForce::Unknown {
+                return Err(OrderBuildError::UnsupportedTimeInForce(self.time_in_force));
+            }
+
+            let mut symbols = HashSet::new();
+            for leg in &self.legs {
+                if leg.instrument_type == InstrumentType::Unknown {
+                    return Err(OrderBuildError::UnsupportedInstrumentType {
+                        symbol: leg.symbol.clone(),
+                        instrument_type: leg.instrument_type,
+                    });
+                }
+                if !leg.quantity.is_positive() {
+                    return Err(OrderBuildError::InvalidQuantity {
+                        symbol: leg.symbol.clone(),
+                    });
+                }
+                if !symbols.insert(&leg.symbol) {
+                    return Err(OrderBuildError::DuplicateLeg {
+                        symbol: leg.symbol.clone(),
+                    });
+                }
+            }
+
+            let (price, price_effect) = match (self.order_type, self.price) {
+                (OrderType::Limit, None) => return Err(OrderBuildError::MissingPrice),
+                (OrderType::Limit, Some((price, price_effect))) => {
+                    let first_leg_effect = self.legs[0].action.value_effect();
+                    let legs_effect = if self
+                        .legs
+                        .iter()
+                        .all(|leg| leg.action.value_effect() == first_leg_effect)
+                    {
+                        Some(first_leg_effect)
+                    } else {
+                        None
+                    };
+                    match legs_effect {
+                        Some(expected) if expected != price_effect => {
+                            return Err(OrderBuildError::PriceEffectMismatch {
+                                expected,
+                                actual: price_effect,
+                            });
+                        }
+                        _ => (Some(price.abs()), Some(price_effect)),
+                    }
+                }
+                _ => (None, None),
+            };
+
+            Ok(NewOrder {
+                time_in_force: self.time_in_force,
+                order_type: self.order_type,
+                price,
+                price_effect,
+                legs: self.legs,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        orders::*,
+        transactions::{TradeAction, ValueEffect},
+        *,
+    };
+
+    use std::str::FromStr;
+
+    #[test]
+    fn test_instrument_type_deserialize() {
+        let instrument_type: InstrumentType = serde_json::from_str(r#""Warrant""#).unwrap();
+        assert_eq!(instrument_type, InstrumentType::Warrant);
+
+        let instrument_type: InstrumentType = serde_json::from_str(r#""Equity Offering""#).unwrap();
+        assert_eq!(instrument_type, InstrumentType::EquityOffering);
+
+        let instrument_type: InstrumentType = serde_json::from_str(r#""Liquidity Pool""#).unwrap();
+        assert_eq!(instrument_type, InstrumentType::Unknown);
+    }
+
+    #[test]
+    fn test_earnings_reported() {
+        let earnings: market_metrics::Earnings = serde_json::from_value(serde_json::json!({
+            "expected-report-date": "2023-05-04",
+            "estimated": false,
+            "time-of-day": "AMC",
+            "actual-eps": "1.52",
+            "consensus-estimate": 1.43,
+            "updated-at": "2023-05-05T01:02:03.000+00:00",
+        }))
+        .unwrap();
+        assert_eq!(earnings.actual_eps, Some(Decimal(Rational64::new(38, 25))));
+        assert_eq!(
+            earnings.consensus_estimate,
+            Some(Decimal(Rational64::new(143, 100)))
+        );
+        assert!(earnings.updated_at.is_some());
+
+        let earnings: market_metrics::Earnings =
+            serde_json::from_value(serde_json::json!({"estimated": true})).unwrap();
+        assert_eq!(earnings.actual_eps, None);
+    }
+
+    #[test]
+    fn test_balances_decimal_as_number_or_string() {
+        let data: balances::Data = serde_json::from_str(
+            r#"{
+                "cash-balance": 100,
+                "net-liquidating-value": "2500.5",
+                "equity-buying-power": 1200.25,
+                "derivative-buying-power": "-3"
+            }"#,
+        )
+        .unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(json["cash-balance"], "100");
+        assert_eq!(json["net-liquidating-value"], "2500.5");
+        assert_eq!(json["equity-buying-power"], "1200.25");
+        assert_eq!(json["derivative-buying-power"], "-3");
+    }
+
+    #[test]
+    fn test_balances_freshness_fields() {
+        let data: balances::Data = serde_json::from_str(
+            r#"{
+                "cash-balance": "100",
+                "net-liquidating-value": "2500.5",
+                "equity-buying-power": "1200.25",
+                "derivative-buying-power": "-3",
+                "pending-cash": "25.0",
+                "pending-cash-effect": "Debit",
+                "snapshot-date": "2023-03-15",
+                "updated-at": "2023-03-15T20:00:00.000+00:00"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data.pending_cash_effect, Some(ValueEffect::Debit));
+        assert_eq!(data.snapshot_date, NaiveDate::from_ymd_opt(2023, 3, 15));
+        assert!(data.updated_at.is_some());
+        assert!(data.day_trade_excess.is_none());
+    }
+
+    #[test]
+    fn test_balances_new_account() {
+        let data: balances::Data = serde_json::from_str(
+            r#"{
+                "cash-balance": "",
+                "net-liquidating-value": null,
+                "net-liquidating-value-effect": "None",
+                "available-trading-funds": ""
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(data.cash_balance.0, Rational64::zero());
+        assert_eq!(data.net_liquidating_value.0, Rational64::zero());
+        assert_eq!(data.equity_buying_power.0, Rational64::zero());
+        assert!(data.available_trading_funds.is_none());
+    }
+
+    #[test]
+    fn test_order_builder_vertical_spread() {
+        let order = OrderBuilder::new()
+            .sell_to_open("SPY   230616P00400000", 1)
+            .buy_to_open("SPY   230616P00395000", 1)
+            .limit_price(Decimal::from_str("1.25").unwrap(), ValueEffect::Credit)
+            .build()
+            .unwrap();
+
+        assert_eq!(order.order_type, OrderType::Limit);
+        assert_eq!(order.price_effect, Some(ValueEffect::Credit));
+        assert_eq!(order.legs.len(), 2);
+        assert_eq!(order.legs[0].instrument_type, InstrumentType::EquityOption);
+        assert_eq!(order.legs[1].action, TradeAction::BuyToOpen);
+    }
+
+    #[test]
+    fn test_order_time_in_force_gtd() {
+        let order = OrderBuilder::new()
+            .buy_to_open("SPY", 10)
+            .limit_price(Decimal::from_str("400").unwrap(), ValueEffect::Debit)
+            .time_in_force(TimeInForce::GTD(
+                NaiveDate::from_ymd_opt(2023, 6, 16).unwrap(),
+            ))
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_value(&order).unwrap();
+        assert_eq!(json["time-in-force"], "GTD");
+        assert_eq!(json["gtc-date"], "2023-06-16");
+
+        let order: NewOrder = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            order.time_in_force,
+            TimeInForce::GTD(NaiveDate::from_ymd_opt(2023, 6, 16).unwrap())
+        );
+
+        let json = serde_json::to_value(
+            OrderBuilder::new()
+                .market()
+                .buy_to_open("SPY", 1)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["time-in-force"], "Day");
+        assert!(json.get("gtc-date").is_none());
+    }
+
+    #[test]
+    fn test_order_newer_types_deserialize() {
+        let order = |order_type: &str, time_in_force: &str| {
+            serde_json::from_value::<Order>(serde_json::json!({
+                "id": 1,
+                "account-number": "5WT00000",
+                "status": "Live",
+                "order-type": order_type,
+                "time-in-force": time_in_force,
+            }))
+            .unwrap()
+        };
+
+        let notional = order("Notional Market", "GTC Ext");
+        assert_eq!(notional.order_type, OrderType::NotionalMarket);
+        assert_eq!(notional.time_in_force, TimeInForce::GTCExt);
+        assert_eq!(order("Market", "IOC").time_in_force, TimeInForce::IOC);
+
+        let unknown = order("Trailing Stop", "FOK");
+        assert_eq!(unknown.order_type, OrderType::Unknown);
+        assert_eq!(unknown.time_in_force, TimeInForce::Unknown);
+    }
+
+    #[test]
+    fn test_order_response_reject_reason() {
+        let response: orders::OrderResponse = serde_json::from_value(serde_json::json!({
+            "order": {
+                "id": 1,
+                "account-number": "5WT00000",
+                "status": "Rejected",
+                "order-type": "Limit",
+                "time-in-force": "Day",
+                "reject-reason": "Insufficient buying power",
+            },
+            "warnings": [{"code": "tif_next_valid_session", "message": "Order will be queued"}],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            response.order.reject_reason.as_deref(),
+            Some("Insufficient buying power")
+        );
+        assert_eq!(response.warnings.len(), 1);
+        assert_eq!(response.warnings[0].message, "Order will be queued");
+        assert_eq!(response.order.signed_price(), None);
+    }
+
+    #[test]
+    fn test_order_signed_price() {
+        let order = |price_effect| {
+            serde_json::from_value::<orders::Order>(serde_json::json!({
+                "id": 1,
+                "account-number": "5WT00000",
+                "status": "Live",
+                "order-type": "Limit",
+                "time-in-force": "Day",
+                "price": "1.25",
+                "price-effect": price_effect,
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(order("Credit").signed_price(), Some(Rational64::new(5, 4)));
+        assert_eq!(order("Debit").signed_price(), Some(Rational64::new(-5, 4)));
+    }
+
+    #[test]
+    fn test_order_builder_price_effect_mismatch() {
+        let result = OrderBuilder::new()
+            .buy_to_open("SPY   230616P00400000", 1)
+            .limit_price(Decimal::from_str("1.25").unwrap(), ValueEffect::Credit)
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(OrderBuildError::PriceEffectMismatch {
+                expected: ValueEffect::Debit,
+                actual: ValueEffect::Credit,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_order_builder_rejects_unknown_variants() {
+        let result = OrderBuilder::new()
+            .market()
+            .buy_to_open("SPY", 1)
+            .time_in_force(TimeInForce::Unknown)
+            .build();
+        assert!(matches!(
+            result,
+            Err(OrderBuildError::UnsupportedTimeInForce(
+                TimeInForce::Unknown
+            ))
+        ));
+
+        let mut leg = Leg::new("SPY", 1, TradeAction::BuyToOpen);
+        leg.instrument_type = InstrumentType::Unknown;
+        let result = OrderBuilder::new().market().leg(leg).build();
+        assert!(matches!(
+            result,
+            Err(OrderBuildError::UnsupportedInstrumentType {
+                instrument_type: InstrumentType::Unknown,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_trade_from_csv_value_signs() {
+        let export = "\
+Date,Type,Action,Symbol,Instrument Type,Description,Value,Quantity,Average Price,Commissions,Fees,Multiplier,Expiration Date,Strike Price,Call or Put
+2023-06-01T10:00:00-04:00,Trade,BUY_TO_OPEN,SPY   230616P00400000,Equity Option,Bought 1 SPY,-215.00,1,-215.00,-1.00,-0.15,100,6/16/23,400,PUT
+2023-06-02T10:00:00-04:00,Trade,SELL_TO_CLOSE,SPY   230616P00400000,Equity Option,Sold 1 SPY,250.00,1,250.00,0.50,0.00,100,6/16/23,400,PUT
+";
+        let trades: Vec<_> = csv::read_transactions(export.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|csv| match transactions::Item::from(csv) {
+                transactions::Item::Trade(trade) => trade,
+                item => panic!("Expected a trade, got {:?}", item),
+            })
+            .collect();
+
+        // commissions and fees keep the sign of the export, so a charge is a debit and a
+        // rebate a credit
+        assert_eq!(trades[0].value(), Rational64::from_integer(-215));
+        assert_eq!(trades[0].commission(), Rational64::from_integer(-1));
+        assert_eq!(trades[0].fees(), Rational64::new(-15, 100));
+        assert_eq!(trades[1].value(), Rational64::from_integer(250));
+        assert_eq!(trades[1].commission(), Rational64::new(1, 2));
+        assert_eq!(trades[1].fees(), Rational64::zero());
+    }
+
+    #[test]
+    fn test_receive_deliver_effective_action() {
+        let assignment = |instrument_type, value_effect| {
+            serde_json::from_value::<transactions::ReceiveDeliver>(serde_json::json!({
+                "id": 1,
+                "symbol": "SPY",
+                "instrument-type": instrument_type,
+                "transaction-sub-type": "Assignment",
+                "executed-at": "2023-06-16T20:00:00.000+00:00",
+                "underlying-symbol": "SPY",
+                "value": "40000.0",
+                "value-effect": value_effect,
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(
+            assignment("Equity Option", "None").effective_action(),
+            Some(TradeAction::BuyToClose)
+        );
+        assert_eq!(
+            assignment("Equity", "Debit").effective_action(),
+            Some(TradeAction::Buy)
+        );
+        assert_eq!(
+            assignment("Equity", "Credit").effective_action(),
+            Some(TradeAction::Sell)
+        );
+    }
+
+    #[test]
+    fn test_link_assignments() {
+        let receive_deliver = |id, symbol: &str, instrument_type, sub_type, executed_at: &str| {
+            serde_json::from_value::<transactions::Item>(serde_json::json!({
+                "transaction-type": "Receive Deliver",
+                "id": id,
+                "symbol": symbol,
+                "instrument-type": instrument_type,
+                "transaction-sub-type": sub_type,
+                "executed-at": executed_at,
+                "underlying-symbol": "SPY",
+                "value": "0.0",
+                "value-effect": "None",
+            }))
+            .unwrap()
+        };
+        let expiration_day = "2023-06-16T20:00:00.000+00:00";
+        let items = vec![
+            receive_deliver(
+                1,
+                "SPY   230616P00400000",
+                "Equity Option",
+                "Assignment",
+                expiration_day,
+            ),
+            receive_deliver(2, "SPY", "Equity", "Exercise", expiration_day),
+            receive_deliver(
+                3,
+                "SPY",
+                "Equity",
+                "Assignment",
+                "2023-06-16T20:00:01.000+00:00",
+            ),
+            receive_deliver(
+                4,
+                "SPY   230616C00420000",
+                "Equity Option",
+                "Expiration",
+                expiration_day,
+            ),
+            receive_deliver(
+                5,
+                "SPY   230616C00380000",
+                "Equity Option",
+                "Exercise",
+                expiration_day,
+            ),
+            receive_deliver(
+                6,
+                "SPY   230623C00380000",
+                "Equity Option",
+                "Exercise",
+                "2023-06-23T20:00:00.000+00:00",
+            ),
+        ];
+
+        let events = transactions::link_assignments(&items);
+        let ids: Vec<_> = events
+            .iter()
+            .map(|event| (event.option.id, event.delivery.as_ref().map(|d| d.id)))
+            .collect();
+        assert_eq!(ids, vec![(1, Some(3)), (5, Some(2)), (6, None)]);
+    }
+
+    #[test]
+    fn test_link_assignments_same_day_strikes() {
+        let receive_deliver = |id, symbol: &str, instrument_type, quantity| {
+            serde_json::from_value::<transactions::Item>(serde_json::json!({
+                "transaction-type": "Receive Deliver",
+                "id": id,
+                "symbol": symbol,
+                "instrument-type": instrument_type,
+                "transaction-sub-type": "Assignment",
+                "executed-at": "2023-06-16T20:00:00.000+00:00",
+                "underlying-symbol": "SPY",
+                "value": "0.0",
+                "value-effect": "None",
+                "quantity": quantity,
+            }))
+            .unwrap()
+        };
+        // the deliveries are in the opposite order of the options they belong to
+        let items = vec![
+            receive_deliver(1, "SPY   230616P00400000", "Equity Option", "1"),
+            receive_deliver(2, "SPY   230616P00410000", "Equity Option", "2"),
+            receive_deliver(3, "SPY", "Equity", "200"),
+            receive_deliver(4, "SPY", "Equity", "100"),
+            // an adjusted option with a delivery of other than 100 shares
+            receive_deliver(5, "SPY1  230616P00420000", "Equity Option", "1"),
+            receive_deliver(6, "SPY", "Equity", "50"),
+        ];
+
+        let events = transactions::link_assignments(&items);
+        let ids: Vec<_> = events
+            .iter()
+            .map(|event| (event.option.id, event.delivery.as_ref().map(|d| d.id)))
+            .collect();
+        assert_eq!(ids, vec![(1, Some(4)), (2, Some(3)), (5, Some(6))]);
+    }
+
+    const SPY_PUT: &str = "SPY   230616P00400000";
+    const SPY_CALL: &str = "SPY   230616C00450000";
+
+    // a put sold to open and partially closed, and a call bought to open, out of order
+    fn spy_option_trades() -> Vec<transactions::Item> {
+        let trade = |id, symbol: &str, action, quantity, executed_at| {
+            serde_json::from_value::<transactions::Item>(serde_json::json!({
+                "transaction-type": "Trade",
+                "id": id,
+                "symbol": symbol,
+                "instrument-type": "Equity Option",
+                "executed-at": executed_at,
+                "action": action,
+                "underlying-symbol": "SPY",
+                "value": "100.0",
+                "value-effect": "Credit",
+                "quantity": quantity,
+                "commission": "1.0",
+                "commission-effect": "Debit",
+                "clearing-fees": "0.1",
+                "clearing-fees-effect": "Debit",
+                "regulatory-fees": "0.02",
+                "regulatory-fees-effect": "Debit",
+                "proprietary-index-option-fees": "0.0",
+                "proprietary-index-option-fees-effect": "None",
+                "ext-global-order-number": id,
+            }))
+            .unwrap()
+        };
+        vec![
+            trade(
+                3,
+                SPY_PUT,
+                "Buy to Close",
+                "1",
+                "2023-03-20T15:00:00.000+00:00",
+            ),
+            trade(
+                1,
+                SPY_PUT,
+                "Sell to Open",
+                "2",
+                "2023-03-01T15:00:00.000+00:00",
+            ),
+            trade(
+                2,
+                SPY_CALL,
+                "Buy to Open",
+                "1",
+                "2023-03-10T15:00:00.000+00:00",
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_positions_as_of() {
+        let transactions = spy_option_trades();
+
+        let positions =
+            positions::as_of(&transactions, NaiveDate::from_ymd_opt(2023, 3, 15).unwrap());
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].symbol, SPY_CALL);
+        assert_eq!(positions[0].signed_quantity(), Rational64::from_integer(1));
+        assert_eq!(positions[1].symbol, SPY_PUT);
+        assert_eq!(positions[1].signed_quantity(), Rational64::from_integer(-2));
+
+        let positions =
+            positions::as_of(&transactions, NaiveDate::from_ymd_opt(2023, 3, 31).unwrap());
+        assert_eq!(positions[1].signed_quantity(), Rational64::from_integer(-1));
+    }
+
+    #[test]
+    fn test_transactions_total_costs() {
+        let costs = transactions::total_costs(&spy_option_trades());
+        assert_eq!(costs.commissions, Rational64::from_integer(-3));
+        assert_eq!(costs.fees, Rational64::new(-36, 100));
+        assert_eq!(costs.total(), Rational64::new(-336, 100));
+    }
+
+    #[test]
+    fn test_transactions_opens() {
+        let transactions = spy_option_trades();
+        assert_eq!(transactions[0].opens(), Some(false));
+        assert_eq!(transactions[1].opens(), Some(true));
+        assert_eq!(transactions[2].opens(), Some(true));
+    }
+
+    #[test]
+    fn test_trade_signed_quantity() {
+        let transactions = spy_option_trades();
+        let signed_quantities: Vec<_> = transactions
+            .iter()
+            .map(|item| match item {
+                transactions::Item::Trade(trade) => trade.signed_quantity(),
+                _ => panic!("Expected a trade"),
+            })
+            .collect();
+        assert_eq!(
+            signed_quantities,
+            vec![
+                Rational64::from_integer(1),
+                Rational64::from_integer(-2),
+                Rational64::from_integer(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trade_share_quantity() {
+        if let transactions::Item::Trade(trade) = &spy_option_trades()[1] {
+            assert_eq!(trade.share_quantity(None), Rational64::from_integer(200));
+            assert_eq!(
+                trade.share_quantity(Some(50)),
+                Rational64::from_integer(100)
+            );
+        } else {
+            panic!("Expected a trade");
+        }
+    }
+
+    #[test]
+    fn test_market_data_instrument_type_parameter() {
+        use market_data::instrument_type_parameter;
+
+        assert_eq!(instrument_type_parameter(InstrumentType::Equity), "equity");
+        assert_eq!(
+            instrument_type_parameter(InstrumentType::EquityOption),
+            "equity-option"
+        );
+        assert_eq!(
+            instrument_type_parameter(InstrumentType::FutureOption),
+            "future-option"
+        );
+    }
+
+    fn long_position(symbol: &str, instrument_type: InstrumentType) -> positions::Item {
+        positions::Item {
+            symbol: symbol.to_string(),
+            quantity: Rational64::from_integer(1),
+            quantity_direction: positions::QuantityDirection::Long,
+            instrument_type,
+            multiplier: None,
+            mark: None,
+            mark_price: None,
+            greeks: None,
+            average_open_price: None,
+        }
+    }
+
+    // stock, options and futures options positions on three underlyings
+    fn mixed_positions() -> Vec<positions::Item> {
+        vec![
+            long_position("SPY", InstrumentType::Equity),
+            long_position("SPY   230616P00400000", InstrumentType::EquityOption),
+            long_position("SPXW  230616P04000000", InstrumentType::EquityOption),
+            long_position("/ESZ3", InstrumentType::Future),
+            long_position("./ESZ3 EW4Z3 231229C4500", InstrumentType::FutureOption),
+        ]
+    }
+
+    #[test]
+    fn test_positions_group_by_underlying() {
+        let positions = mixed_positions();
+
+        let groups = positions::group_by_underlying(&positions);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups["SPY"].len(), 2);
+        assert_eq!(groups["SPX"].len(), 1);
+        assert_eq!(groups["/ESZ3"].len(), 2);
+    }
+
+    #[test]
+    fn test_positions_diff() {
+        let positions = mixed_positions();
+        let mut new_positions = positions[1..4].to_vec();
+        new_positions[0].quantity_direction = positions::QuantityDirection::Short;
+        new_positions.push(long_position("QQQ", InstrumentType::Equity));
+        let changes = positions::diff(&positions, &new_positions);
+        let symbols: Vec<_> = changes.iter().map(|change| change.symbol()).collect();
+        assert_eq!(
+            symbols,
+            vec![
+                "./ESZ3 EW4Z3 231229C4500",
+                "QQQ",
+                "SPY",
+                "SPY   230616P00400000"
+            ]
+        );
+        assert!(matches!(changes[0], positions::PositionChange::Closed(_)));
+        assert!(matches!(changes[1], positions::PositionChange::Opened(_)));
+        assert!(matches!(
+            changes[3],
+            positions::PositionChange::QuantityChanged { .. }
+        ));
+    }
+
+    #[test]
+    fn test_futures_option_chain_option_roots() {
+        let expiration = |option_root_symbol: &str, expiration_date: &str| {
+            serde_json::json!({
+                "underlying-symbol": "/ESZ3",
+                "root-symbol": "/ES",
+                "option-root-symbol": option_root_symbol,
+                "expiration-date": expiration_date,
+                "days-to-expiration": 10,
+                "expiration-type": "Weekly",
+            })
+        };
+        let chain: futures_option_chains::Item = serde_json::from_value(serde_json::json!({
+            "underlying-symbol": "ES",
+            "root-symbol": "/ES",
+            "expirations": [
+                expiration("EW", "2023-11-30"),
+                expiration("E1A", "2023-11-03"),
+                expiration("EW", "2023-12-29"),
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(chain.option_roots(), vec!["E1A", "EW"]);
+        assert_eq!(chain.expirations_for_root("EW").count(), 2);
+    }
+
+    #[test]
+    fn test_futures_option_chain_streamer_symbol() {
+        let chain: futures_option_chains::Item = serde_json::from_value(serde_json::json!({
+            "underlying-symbol": "ES",
+            "root-symbol": "/ES",
+            "expirations": [{
+                "underlying-symbol": "/ESZ3",
+                "root-symbol": "/ES",
+                "option-root-symbol": "EW",
+                "expiration-date": "2023-11-30",
+                "days-to-expiration": 10,
+                "expiration-type": "Weekly",
+                "strikes": [{
+                    "strike-price": "4500.0",
+                    "call": "./ESZ3 EW4X3 231130C4500",
+                    "call-streamer-symbol": "./EW4X23C4500:XCME",
+                    "put": "./ESZ3 EW4X3 231130P4500",
+                    "put-streamer-symbol": "./EW4X23P4500:XCME",
+                }],
+            }],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            chain.streamer_symbol("./ESZ3 EW4X3 231130C4500"),
+            Some("./EW4X23C4500:XCME")
+        );
+        assert_eq!(
+            chain.streamer_symbol("./ESZ3 EW4X3 231130P4500"),
+            Some("./EW4X23P4500:XCME")
+        );
+        assert_eq!(chain.streamer_symbol("./ESZ3 EW4X3 231130P4400"), None);
+    }
+
+    #[test]
+    fn test_expiration_strike_prices() {
+        let expiration: option_chains::Expiration = serde_json::from_value(serde_json::json!({
+            "expiration-type": "Regular",
+            "expiration-date": "2023-06-16",
+            "days-to-expiration": 30,
+            "settlement-type": "PM",
+            "strikes": [
+                {"strike-price": "105.0", "call": "XYZ   230616C00105000", "put": "XYZ   230616P00105000"},
+                {"strike-price": "95.5", "call": "XYZ   230616C00095500", "put": "XYZ   230616P00095500"},
+                {"strike-price": "105.0", "call": "XYZ   230616C00105000", "put": "XYZ   230616P00105000"},
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            expiration.strike_prices(),
+            vec![Rational64::new(191, 2), Rational64::from_integer(105)]
+        );
+
+        let mut strikes = expiration.strikes;
+        strikes.sort();
+        assert_eq!(strikes[0].strike_price, Decimal(Rational64::new(191, 2)));
+        assert_eq!(strikes[1], strikes[2]);
+    }
+
+    #[test]
+    fn test_expiration_settlement() {
+        let expiration = |expiration_type: &str, settlement_type: &str| {
+            serde_json::from_value::<option_chains::Expiration>(serde_json::json!({
+                "expiration-type": expiration_type,
+                "expiration-date": "2023-06-30",
+                "days-to-expiration": 30,
+                "settlement-type": settlement_type,
+            }))
+            .unwrap()
+        };
+
+        let monthly = expiration("Regular", "AM");
+        assert!(monthly.is_am_settled());
+        assert!(!monthly.is_end_of_month());
+
+        let end_of_month = expiration("End-Of-Month", "PM");
+        assert!(!end_of_month.is_am_settled());
+        assert!(end_of_month.is_end_of_month());
+    }
+
+    #[test]
+    fn test_group_futures_by_product() {
+        let future = |symbol: &str, expiration_date: &str, active_month: bool| {
+            serde_json::from_value::<instruments::Future>(serde_json::json!({
+                "symbol": symbol,
+                "product-code": &symbol[1..3],
+                "expiration-date": expiration_date,
+                "active": true,
+                "active-month": active_month,
+            }))
+            .unwrap()
+        };
+        let products = instruments::group_by_product(vec![
+            future("/NQM3", "2023-06-16", true),
+            future("/ESU3", "2023-09-15", false),
+            future("/ESM3", "2023-06-16", true),
+        ]);
+
+        assert_eq!(products.len(), 2);
+        assert_eq!(products[0].product_code, "ES");
+        assert_eq!(products[0].active_symbols(), vec!["/ESM3", "/ESU3"]);
+        assert_eq!(
+            products[0]
+                .front_month()
+                .map(|future| future.symbol.as_str()),
+            Some("/ESM3")
+        );
+        assert!(products[1].next_active_month().is_none());
+    }
+
+    #[test]
+    fn test_round_to_tick() {
+        use option_chains::{round_to_tick, TickSize};
+
+        let tick_sizes: Vec<TickSize> =
+            serde_json::from_str(r#"[{"value": "0.05", "threshold": "3.0"}, {"value": "0.1"}]"#)
+                .unwrap();
+        let round = |price| round_to_tick(Decimal::from_str(price).unwrap(), &tick_sizes).0;
+
+        assert_eq!(round("1.23"), Rational64::new(5, 4));
+        assert_eq!(round("2.99"), Rational64::from_integer(3));
+        assert_eq!(round("3.14"), Rational64::new(31, 10));
+        assert_eq!(round("3.16"), Rational64::new(32, 10));
+    }
+
+    #[test]
+    fn test_money_movement_type() {
+        use transactions::MoneyMovementType;
+
+        let sub_type: MoneyMovementType = serde_json::from_str(r#""Credit Interest""#).unwrap();
+        assert_eq!(sub_type, MoneyMovementType::CreditInterest);
+
+        let sub_type: MoneyMovementType = serde_json::from_str(r#""Mark to Market""#).unwrap();
+        assert_eq!(sub_type, MoneyMovementType::Unknown);
+
+        let item: transactions::Item = serde_json::from_value(serde_json::json!({
+            "transaction-type": "Money Movement",
+            "transaction-sub-type": "Mark to Market",
+            "id": 1,
+            "executed-at": "2023-03-01T15:00:00.000+00:00",
+            "value": "12.5",
+            "value-effect": "Credit",
+        }))
+        .unwrap();
+        if let transactions::Item::MoneyMovement(money_movement) = &item {
+            assert_eq!(
+                money_movement.transaction_sub_type,
+                Some(MoneyMovementType::Unknown)
+            );
+            assert_eq!(
+                money_movement.raw_transaction_sub_type(),
+                Some("Mark to Market")
+            );
+        } else {
+            panic!("Expected a money movement");
+        }
+
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["transaction-sub-type"], "Mark to Market");
+    }
+
+    #[test]
+    fn test_signed_quantity() {
+        use positions::{QuantityDirection, SignedQuantity};
+
+        let quantity = SignedQuantity::from_value(Rational64::from_integer(-2));
+        assert_eq!(quantity.quantity, Rational64::from_integer(2));
+        assert_eq!(quantity.direction, QuantityDirection::Short);
+        assert_eq!(quantity.value(), Rational64::from_integer(-2));
+
+        let leg = orders::Leg::new("SPY", 3, transactions::TradeAction::SellToOpen);
+        assert_eq!(leg.signed_quantity(), Rational64::from_integer(-3));
+    }
+
+    #[test]
+    fn test_quantity_direction_from_str() {
+        use positions::QuantityDirection;
+
+        assert_eq!("short".parse(), Ok(QuantityDirection::Short));
+        assert_eq!(" Long".parse(), Ok(QuantityDirection::Long));
+        assert!("flat".parse::<QuantityDirection>().is_err());
+        assert_eq!(QuantityDirection::Short.to_string(), "Short");
+    }
+
+    #[test]
+    fn test_positions_break_even() {
+        let position = |symbol: &str, instrument_type, quantity_direction| {
+            serde_json::from_value::<positions::Item>(serde_json::json!({
+                "symbol": symbol,
+                "quantity": 1,
+                "quantity-direction": quantity_direction,
+                "instrument-type": instrument_type,
+                "average-open-price": "2.15",
+            }))
+            .unwrap()
+        };
+
+        let call = position("SPY   230616C00400000", "Equity Option", "Long");
+        assert_eq!(call.break_even(), Some(Rational64::new(40215, 100)));
+        let put = position("SPY   230616P00400000", "Equity Option", "Short");
+        assert_eq!(put.break_even(), Some(Rational64::new(39785, 100)));
+        let shares = position("SPY", "Equity", "Long");
+        assert_eq!(shares.break_even(), None);
+    }
+
+    #[test]
+    fn test_positions_fill_multipliers() {
+        let chain = |root_symbol: &str, shares_per_contract| {
+            serde_json::from_value::<option_chains::Item>(serde_json::json!({
+                "underlying-symbol": "XYZ",
+                "root-symbol": root_symbol,
+                "option-chain-type": if root_symbol == "XYZ" { "Standard" } else { "Non-standard" },
+                "shares-per-contract": shares_per_contract,
+                "deliverables": [],
+                "expirations": [{
+                    "expiration-type": "Regular",
+                    "expiration-date": "2023-06-16",
+                    "days-to-expiration": 30,
+                    "settlement-type": "PM",
+                }],
+            }))
+            .unwrap()
+        };
+        let chains = vec![chain("XYZ", 100), chain("XYZ1", 150)];
+        let position = |symbol: &str| positions::Item {
+            symbol: symbol.to_string(),
+            quantity: Rational64::from_integer(1),
+            quantity_direction: positions::QuantityDirection::Long,
+            instrument_type: InstrumentType::EquityOption,
+            multiplier: None,
+            mark: None,
+            mark_price: None,
+            greeks: None,
+            average_open_price: None,
+        };
+        let mut positions = vec![
+            position("XYZ   230616C00050000"),
+            position("XYZ1  230616C00050000"),
+            position("XYZ1  230623C00050000"),
+        ];
+
+        positions::fill_multipliers(&mut positions, &chains);
+        assert_eq!(positions[0].multiplier, Some(Rational64::from_integer(100)));
+        assert_eq!(positions[1].multiplier, Some(Rational64::from_integer(150)));
+        assert_eq!(positions[2].multiplier, None);
+        assert_eq!(
+            positions[1].notional_value(Rational64::from_integer(2)),
+            Some(Rational64::from_integer(300))
+        );
+    }
 }
@@ -0,0 +1,85 @@
+use crate::{api::option_chains, errors::ApiError, session::Session};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// An in-memory cache of [`option_chains()`](crate::option_chains()) by symbol. Chains are
+/// fetched again once they are older than the TTL, while prices should be streamed instead.
+/// Concurrent requests for a symbol that is not cached may each fetch it.
+pub struct OptionChainCache {
+    ttl: Duration,
+    chains: Mutex<HashMap<String, CachedChains>>,
+}
+
+// the chains of a symbol and when they were fetched
+type CachedChains = (Instant, Arc<Vec<option_chains::Item>>);
+
+impl OptionChainCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            chains: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached chains of `symbol` if fetched within the TTL, otherwise fetches them.
+    pub async fn option_chains(
+        &self,
+        symbol: &str,
+        session: &Session,
+    ) -> Result<Arc<Vec<option_chains::Item>>, ApiError> {
+        if let Some((fetched_at, chains)) = self.chains.lock().unwrap().get(symbol) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(chains.clone());
+            }
+        }
+
+        let chains = Arc::new(crate::option_chains(symbol, session).await?);
+        self.chains
+            .lock()
+            .unwrap()
+            .insert(symbol.to_string(), (Instant::now(), chains.clone()));
+        Ok(chains)
+    }
+
+    /// Removes the chains of `symbol`, e.g. after a corporate action adjusted them.
+    pub fn invalidate(&self, symbol: &str) {
+        self.chains.lock().unwrap().remove(symbol);
+    }
+
+    pub fn clear(&self) {
+        self.chains.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+    use reqwest::Method;
+
+    #[tokio::test]
+    async fn test_option_chain_cache() {
+        let chain = r#"{"data":{"items":[{"underlying-symbol":"SPY","root-symbol":"SPY",
+            "option-chain-type":"Standard","shares-per-contract":100,"deliverables":[],
+            "expirations":[]}]}}"#;
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            Method::GET,
+            "option-chains/SPY/nested",
+            chain,
+        ));
+        let offline = Session::from_token("token").with_transport(MockTransport::new());
+
+        let cache = OptionChainCache::new(Duration::from_secs(60));
+        assert_eq!(cache.option_chains("SPY", &session).await.unwrap().len(), 1);
+        assert_eq!(cache.option_chains("SPY", &offline).await.unwrap().len(), 1);
+
+        cache.invalidate("SPY");
+        assert!(cache.option_chains("SPY", &offline).await.is_err());
+
+        let cache = OptionChainCache::new(Duration::from_secs(0));
+        cache.option_chains("SPY", &session).await.unwrap();
+        assert!(cache.option_chains("SPY", &offline).await.is_err());
+    }
+}
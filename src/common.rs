@@ -1,12 +1,145 @@
 pub use options_common::{Decimal, ExpirationDate, OptionType};
 
+use crate::errors::ParseEnumError;
+
+use chrono::NaiveDate;
 use num_rational::Rational64;
+use num_traits::Zero;
 use serde::{de, Deserialize, Deserializer, Serializer};
 
 use std::convert::TryInto;
 use std::fmt::{self, Display};
 use std::str::FromStr;
 
+/// Extension methods for [`ExpirationDate`], which is defined in `options_common`.
+pub trait ExpirationDateExt {
+    fn naive_date(&self) -> NaiveDate;
+}
+
+impl ExpirationDateExt for ExpirationDate {
+    fn naive_date(&self) -> NaiveDate {
+        self.0
+    }
+}
+
+/// Parses a date in any of the formats used by the crate's sources: `2023-06-16` in the API,
+/// `230616` in option symbols and `6/16/23` in CSV exports. Four digit years are also accepted
+/// in the latter two, e.g. `20230616` or `6/16/2023`.
+pub fn parse_date(s: &str) -> Result<NaiveDate, chrono::ParseError> {
+    let format = if s.contains('-') {
+        "%Y-%m-%d"
+    } else if let Some((_, year)) = s.rsplit_once('/') {
+        if year.len() == 4 {
+            "%-m/%-d/%Y"
+        } else {
+            "%-m/%-d/%y"
+        }
+    } else if s.len() == 8 {
+        "%Y%m%d"
+    } else {
+        "%y%m%d"
+    };
+    NaiveDate::parse_from_str(s, format)
+}
+
+/// Conversions between [`OptionType`] and strings, in place of the `Display` and `FromStr`
+/// impls that cannot be added to a type defined in `options_common`.
+pub trait OptionTypeExt: Sized {
+    /// `Call` or `Put`.
+    fn as_str(&self) -> &'static str;
+
+    /// Parses `Call`, `Put`, `C` or `P` case-insensitively.
+    fn parse(s: &str) -> Result<Self, ParseEnumError>;
+}
+
+impl OptionTypeExt for OptionType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OptionType::Call => "Call",
+            OptionType::Put => "Put",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self, ParseEnumError> {
+        parse_option_type(s).ok_or_else(|| ParseEnumError {
+            type_name: "option type",
+            value: s.to_string(),
+        })
+    }
+}
+
+// maximum number of decimal places shown for fractional quantities
+const QUANTITY_DECIMALS: usize = 4;
+// beyond this the digits are padded with zeros, as 10^19 does not fit in an i64
+const MAX_DECIMALS: usize = 18;
+
+/// Formats a monetary value rounded to `decimals` places, e.g. `-$1,234.50`. Places beyond
+/// the 18th are always zero.
+pub fn format_currency(value: Rational64, decimals: usize) -> String {
+    let (negative, integer, fraction) = round_parts(value, decimals);
+    let integer = integer.to_string();
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+
+    format!(
+        "{}${}{}",
+        if negative { "-" } else { "" },
+        grouped,
+        fraction.map(|f| format!(".{}", f)).unwrap_or_default()
+    )
+}
+
+/// Formats a quantity as an integer when it is whole, otherwise as a decimal with up to four
+/// places, e.g. `-3` or `0.5`.
+pub fn format_quantity(value: Rational64) -> String {
+    if value.is_integer() {
+        return value.to_integer().to_string();
+    }
+
+    let (negative, integer, fraction) = round_parts(value, QUANTITY_DECIMALS);
+    let fraction = fraction.unwrap_or_default();
+    let fraction = fraction.trim_end_matches('0');
+    format!(
+        "{}{}{}{}",
+        if negative { "-" } else { "" },
+        integer,
+        if fraction.is_empty() { "" } else { "." },
+        fraction
+    )
+}
+
+// the sign, integer part and zero padded fractional part of the value rounded to `decimals`
+fn round_parts(value: Rational64, decimals: usize) -> (bool, i128, Option<String>) {
+    // scaled in i128, as the value times the scale can overflow an i64
+    let scale = 10i128.pow(decimals.min(MAX_DECIMALS) as u32);
+    let numer = i128::from(*value.numer()) * scale;
+    let denom = i128::from(*value.denom());
+    let mut scaled = numer / denom;
+    // round half away from zero, as Rational64::round does
+    if 2 * (numer % denom).abs() >= denom.abs() {
+        scaled += numer.signum() * denom.signum();
+    }
+    let fraction = (scaled.abs() % scale).to_string();
+    (
+        scaled < 0,
+        scaled.abs() / scale,
+        if decimals == 0 {
+            None
+        } else {
+            Some(format!(
+                "{:0<width$}",
+                format!("{:0>width$}", fraction, width = decimals.min(MAX_DECIMALS)),
+                width = decimals
+            ))
+        },
+    )
+}
+
 pub mod string_serialize {
     use super::*;
 
@@ -58,6 +191,124 @@ pub mod optional_string_serialize {
     }
 }
 
+/// Serializes a [`Decimal`] as a string and deserializes it from a string or a number, as
+/// the API is inconsistent about which it returns.
+pub mod decimal_serialize {
+    use super::*;
+
+    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        string_serialize::serialize(value, serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_integer_or_string_as_decimal(deserializer).map(Decimal)
+    }
+}
+
+pub mod optional_decimal_serialize {
+    use super::*;
+
+    pub fn serialize<S>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        optional_string_serialize::serialize(value, serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_optional_integer_or_string_as_decimal(deserializer)
+            .map(|value| value.map(Decimal))
+    }
+}
+
+/// Serializes an [`ExpirationDate`] as `YYYY-MM-DD` and deserializes it with [`parse_date`].
+pub mod expiration_date_serialize {
+    use super::*;
+
+    pub fn serialize<S>(value: &ExpirationDate, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        string_serialize::serialize(value, serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<ExpirationDate, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let string = String::deserialize(deserializer)?;
+        parse_date(&string)
+            .map(ExpirationDate)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Serializes an [`OptionType`] as `Call` or `Put` and deserializes it case-insensitively,
+/// as CSV exports and the API differ, e.g. `CALL`, `Call` or `C`.
+pub mod option_type_serialize {
+    use super::*;
+
+    pub fn serialize<S>(value: &OptionType, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(value.as_str())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OptionType, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let string = String::deserialize(deserializer)?;
+        OptionType::parse(&string).map_err(de::Error::custom)
+    }
+}
+
+pub mod optional_option_type_serialize {
+    use super::*;
+
+    pub fn serialize<S>(value: &Option<OptionType>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => option_type_serialize::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<OptionType>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let string: Option<String> = Option::deserialize(deserializer)?;
+        string
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                parse_option_type(&s)
+                    .ok_or_else(|| de::Error::custom(format!("invalid option type: {}", s)))
+            })
+            .transpose()
+    }
+}
+
+fn parse_option_type(s: &str) -> Option<OptionType> {
+    match s.trim().to_ascii_uppercase().as_str() {
+        "CALL" | "C" => Some(OptionType::Call),
+        "PUT" | "P" => Some(OptionType::Put),
+        _ => None,
+    }
+}
+
 pub fn deserialize_integer_or_string_as_decimal<'de, D>(
     deserializer: D,
 ) -> Result<Rational64, D::Error>
@@ -67,13 +318,45 @@ where
     deserializer.deserialize_any(DeserializeIntegerOrStringAsDecimal)
 }
 
+/// Deserializes `null` and empty strings as `None`.
+pub fn deserialize_optional_integer_or_string_as_decimal<'de, D>(
+    deserializer: D,
+) -> Result<Option<Rational64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(DeserializeOptionalIntegerOrStringAsDecimal)
+}
+
+/// Serializes a [`Decimal`] as a string and deserializes it like [`decimal_serialize`], except
+/// that `null` and empty strings are zero. Use with `#[serde(default)]` for fields that may be
+/// absent, e.g. the balances of a new account.
+pub mod decimal_or_zero_serialize {
+    use super::*;
+
+    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        string_serialize::serialize(value, serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_optional_integer_or_string_as_decimal(deserializer)
+            .map(|value| Decimal(value.unwrap_or_else(Rational64::zero)))
+    }
+}
+
 struct DeserializeIntegerOrStringAsDecimal;
 
 impl<'de> de::Visitor<'de> for DeserializeIntegerOrStringAsDecimal {
     type Value = Rational64;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("an integer or a string")
+        formatter.write_str("a number or a string")
     }
 
     fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
@@ -90,6 +373,13 @@ impl<'de> de::Visitor<'de> for DeserializeIntegerOrStringAsDecimal {
         self.visit_i64(v.try_into().map_err(de::Error::custom)?)
     }
 
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_str(&v.to_string())
+    }
+
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: de::Error,
@@ -99,3 +389,134 @@ impl<'de> de::Visitor<'de> for DeserializeIntegerOrStringAsDecimal {
             .map_err(de::Error::custom)
     }
 }
+
+struct DeserializeOptionalIntegerOrStringAsDecimal;
+
+impl<'de> de::Visitor<'de> for DeserializeOptionalIntegerOrStringAsDecimal {
+    type Value = Option<Rational64>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number, a string or null")
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        DeserializeIntegerOrStringAsDecimal.visit_i64(v).map(Some)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        DeserializeIntegerOrStringAsDecimal.visit_u64(v).map(Some)
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        DeserializeIntegerOrStringAsDecimal.visit_f64(v).map(Some)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        if v.trim().is_empty() {
+            Ok(None)
+        } else {
+            DeserializeIntegerOrStringAsDecimal.visit_str(v).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_currency() {
+        assert_eq!(format_currency(Rational64::new(24691, 20), 2), "$1,234.55");
+        assert_eq!(format_currency(Rational64::new(-5, 2), 2), "-$2.50");
+        assert_eq!(
+            format_currency(Rational64::from_integer(1_000_000), 0),
+            "$1,000,000"
+        );
+        assert_eq!(format_currency(Rational64::new(-1, 1000), 2), "$0.00");
+        assert_eq!(format_currency(Rational64::new(-5, 1000), 2), "-$0.01");
+        assert_eq!(
+            format_currency(Rational64::new(1, 3), 20),
+            "$0.33333333333333333300"
+        );
+        assert_eq!(
+            format_currency(Rational64::from_integer(i64::MAX), 2),
+            "$9,223,372,036,854,775,807.00"
+        );
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 16).unwrap();
+        for s in &["2023-06-16", "230616", "20230616", "6/16/23", "06/16/2023"] {
+            assert_eq!(parse_date(s), Ok(date), "{}", s);
+        }
+        assert!(parse_date("16/6/23").is_err());
+        assert!(parse_date("2023-6").is_err());
+    }
+
+    #[test]
+    fn test_format_quantity() {
+        assert_eq!(format_quantity(Rational64::from_integer(-3)), "-3");
+        assert_eq!(format_quantity(Rational64::new(1, 2)), "0.5");
+        assert_eq!(format_quantity(Rational64::new(-4, 3)), "-1.3333");
+    }
+
+    #[test]
+    fn test_option_type_deserialize_case_insensitive() {
+        #[derive(Deserialize)]
+        struct Row(
+            #[serde(with = "option_type_serialize")] OptionType,
+            #[serde(with = "optional_option_type_serialize")] Option<OptionType>,
+        );
+
+        let row: Row = serde_json::from_str(r#"["CALL", "Put"]"#).unwrap();
+        assert_eq!(row.0, OptionType::Call);
+        assert_eq!(row.1, Some(OptionType::Put));
+
+        let row: Row = serde_json::from_str(r#"["p", ""]"#).unwrap();
+        assert_eq!(row.0, OptionType::Put);
+        assert_eq!(row.1, None);
+
+        assert!(serde_json::from_str::<Row>(r#"["Straddle", null]"#).is_err());
+
+        assert_eq!(OptionType::parse("put"), Ok(OptionType::Put));
+        assert_eq!(OptionType::Call.as_str(), "Call");
+        assert_eq!(
+            OptionType::parse("Straddle").unwrap_err().to_string(),
+            "Invalid option type: Straddle"
+        );
+    }
+}
@@ -1,14 +1,77 @@
 use crate::{
-    common::{optional_string_serialize, string_serialize, Decimal, ExpirationDate, OptionType},
+    api::positions::{QuantityDirection, SignedQuantity},
+    common::{
+        option_type_serialize, optional_option_type_serialize, optional_string_serialize,
+        parse_date, string_serialize, Decimal, ExpirationDate, OptionType,
+    },
     symbol::{self, OptionSymbol},
 };
 
 use chrono::{DateTime, FixedOffset, NaiveDate};
-use serde::Deserialize;
+use num_rational::Rational64;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use std::io;
 use std::str::FromStr;
 
-#[derive(Debug, Deserialize)]
+/// Reads the positions from a positions CSV export, skipping blank and summary rows.
+pub fn read_positions<R: io::Read>(reader: R) -> Result<Vec<Position>, ::csv::Error> {
+    read_records(reader)
+}
+
+/// Reads the transactions from a transactions CSV export, skipping blank and summary rows.
+pub fn read_transactions<R: io::Read>(reader: R) -> Result<Vec<Transaction>, ::csv::Error> {
+    read_records(reader)
+}
+
+/// Writes positions in the layout of a positions CSV export.
+pub fn write_positions<W: io::Write>(
+    writer: W,
+    positions: &[Position],
+) -> Result<(), ::csv::Error> {
+    let mut writer = ::csv::Writer::from_writer(writer);
+    for position in positions {
+        writer.serialize(position)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// blank rows are skipped, as are rows that cannot be read after the last record e.g. the
+// summary at the end of an export, while unreadable rows between records are an error
+fn read_records<T, R>(reader: R) -> Result<Vec<T>, ::csv::Error>
+where
+    T: DeserializeOwned,
+    R: io::Read,
+{
+    let mut reader = ::csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(::csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+
+    let mut records = vec![];
+    let mut unreadable_row = None;
+    for row in reader.records() {
+        let row = row?;
+        if row.iter().all(str::is_empty) {
+            continue;
+        }
+
+        match row.deserialize(Some(&headers)) {
+            Ok(record) => match unreadable_row.take() {
+                Some(e) => return Err(e),
+                None => records.push(record),
+            },
+            Err(e) => {
+                unreadable_row.get_or_insert(e);
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Position {
     #[serde(rename = "Symbol")]
     pub symbol: String,
@@ -18,8 +81,8 @@ pub struct Position {
     pub quantity: i32,
     #[serde(rename = "Strike Price", with = "string_serialize")]
     pub strike_price: Decimal,
-    #[serde(rename = "Call/Put")]
-    pub call_or_put: OptionTypePascalCase,
+    #[serde(rename = "Call/Put", with = "option_type_serialize")]
+    pub call_or_put: OptionType,
     #[serde(rename = "D's Opn")]
     pub days_open: String,
     #[serde(rename = "NetLiq", with = "string_serialize")]
@@ -35,15 +98,20 @@ impl Position {
         OptionSymbol::from(&self.symbol).underlying_symbol()
     }
 
-    pub fn days_open(&self) -> i32 {
-        let idx = self.days_open.len() - 1;
+    /// The quantity, which exports already sign as described by [`SignedQuantity`].
+    pub fn signed_quantity(&self) -> Rational64 {
+        Rational64::from_integer(self.quantity.into())
+    }
 
-        assert!(self.days_open.chars().nth(idx) == Some('d'));
-        self.days_open
-            .get(..idx)
-            .map(|s| i32::from_str(s).ok())
-            .flatten()
-            .unwrap_or_else(|| panic!("Could not parse days open: {}", self.days_open))
+    /// The days the position has been open, e.g. 12 for `12d`, or `None` if the value is empty
+    /// or not in that format.
+    pub fn days_open(&self) -> Option<i32> {
+        let days_open = self.days_open.trim();
+        let days = days_open
+            .strip_suffix('d')
+            .or_else(|| days_open.strip_suffix('D'))
+            .unwrap_or(days_open);
+        i32::from_str(days.trim()).ok()
     }
 }
 
@@ -78,11 +146,22 @@ pub struct Transaction {
     pub expiration_date: Option<TransactionExpiration>,
     #[serde(rename = "Strike Price", with = "optional_string_serialize")]
     pub strike_price: Option<Decimal>,
-    #[serde(rename = "Call or Put")]
-    pub call_or_put: Option<OptionTypeUpperCase>,
+    #[serde(rename = "Call or Put", with = "optional_option_type_serialize")]
+    pub call_or_put: Option<OptionType>,
 }
 
 impl Transaction {
+    /// The signed quantity if the transaction has an action, see [`SignedQuantity`].
+    pub fn signed_quantity(&self) -> Option<Rational64> {
+        self.action.map(|action| {
+            let direction = match action {
+                TradeAction::BuyToOpen | TradeAction::BuyToClose => QuantityDirection::Long,
+                TradeAction::SellToOpen | TradeAction::SellToClose => QuantityDirection::Short,
+            };
+            SignedQuantity::new(self.quantity.0, direction).value()
+        })
+    }
+
     pub fn underlying_symbol(&self) -> Option<&str> {
         self.symbol.as_ref().map(|symbol| {
             let underlying_symbol = symbol
@@ -110,10 +189,7 @@ impl FromStr for TransactionExpiration {
     type Err = chrono::ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(TransactionExpiration(NaiveDate::parse_from_str(
-            s,
-            "%-m/%-d/%y",
-        )?))
+        Ok(TransactionExpiration(parse_date(s)?))
     }
 }
 
@@ -123,41 +199,140 @@ impl From<TransactionExpiration> for ExpirationDate {
     }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize)]
-#[serde(rename_all = "UPPERCASE")]
-pub enum OptionTypeUpperCase {
-    Call,
-    Put,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
 
-impl From<OptionTypeUpperCase> for OptionType {
-    fn from(json: OptionTypeUpperCase) -> OptionType {
-        match json {
-            OptionTypeUpperCase::Call => OptionType::Call,
-            OptionTypeUpperCase::Put => OptionType::Put,
-        }
+    #[test]
+    fn test_read_positions_skips_non_data_rows() {
+        let export = "\
+Symbol,Type,Quantity,Strike Price,Call/Put,D's Opn,NetLiq
+SPY   230616P00400000,OPTION,-1,400,PUT,12d,-125.00
+
+QQQ   230616C00350000,OPTION,2,350,Call,3d,410.00
+Total,,,,,,285.00
+";
+        let positions = read_positions(export.as_bytes()).unwrap();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].call_or_put, OptionType::Put);
+        assert_eq!(positions[0].days_open(), Some(12));
+        assert_eq!(positions[1].quantity, 2);
     }
-}
 
-#[derive(Copy, Clone, Debug, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub enum OptionTypePascalCase {
-    Call,
-    Put,
-}
+    #[test]
+    fn test_write_positions_from_api() {
+        use crate::api::{positions, InstrumentType};
 
-impl From<OptionTypePascalCase> for OptionType {
-    fn from(json: OptionTypePascalCase) -> OptionType {
-        match json {
-            OptionTypePascalCase::Call => OptionType::Call,
-            OptionTypePascalCase::Put => OptionType::Put,
+        let position = positions::Item {
+            symbol: "SPY   230616P00400000".to_string(),
+            quantity: Rational64::from_integer(3),
+            quantity_direction: positions::QuantityDirection::Short,
+            instrument_type: InstrumentType::EquityOption,
+            multiplier: Some(Rational64::from_integer(100)),
+            mark: None,
+            mark_price: None,
+            greeks: None,
+            average_open_price: None,
+        };
+
+        let mut export = vec![];
+        write_positions(&mut export, &[Position::try_from(position).unwrap()]).unwrap();
+        let positions = read_positions(export.as_slice()).unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].instrument_type, "OPTION");
+        assert_eq!(positions[0].quantity, -3);
+        assert_eq!(positions[0].strike_price.0, Rational64::from_integer(400));
+        assert_eq!(positions[0].call_or_put, OptionType::Put);
+        assert_eq!(positions[0].days_open(), None);
+
+        assert_eq!(positions[0].signed_quantity(), Rational64::from_integer(-3));
+        let item = positions::Item::from(positions.into_iter().next().unwrap());
+        assert_eq!(item.signed_quantity(), Rational64::from_integer(-3));
+    }
+
+    #[test]
+    fn test_position_from_api_rejects_unsupported_instrument_types() {
+        use crate::api::{positions, InstrumentType};
+        use crate::errors::PositionExportError;
+
+        let position = |instrument_type| positions::Item {
+            symbol: "/ESZ3".to_string(),
+            quantity: Rational64::from_integer(1),
+            quantity_direction: positions::QuantityDirection::Long,
+            instrument_type,
+            multiplier: None,
+            mark: None,
+            mark_price: None,
+            greeks: None,
+            average_open_price: None,
+        };
+
+        for instrument_type in [
+            InstrumentType::Future,
+            InstrumentType::FutureOption,
+            InstrumentType::Cryptocurrency,
+            InstrumentType::Unknown,
+        ] {
+            assert!(matches!(
+                Position::try_from(position(instrument_type)),
+                Err(PositionExportError::UnsupportedInstrumentType(t)) if t == instrument_type
+            ));
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_position_from_api_rejects_invalid_quantities() {
+        use crate::api::{positions, InstrumentType};
+        use crate::errors::PositionExportError;
+
+        let position = |quantity| positions::Item {
+            symbol: "SPY".to_string(),
+            quantity,
+            quantity_direction: positions::QuantityDirection::Long,
+            instrument_type: InstrumentType::Equity,
+            multiplier: None,
+            mark: None,
+            mark_price: None,
+            greeks: None,
+            average_open_price: None,
+        };
+
+        assert!(matches!(
+            Position::try_from(position(Rational64::new(1, 2))),
+            Err(PositionExportError::InvalidQuantity { quantity, .. })
+                if quantity == Rational64::new(1, 2)
+        ));
+        assert!(matches!(
+            Position::try_from(position(Rational64::from_integer(i64::from(i32::MAX) + 1))),
+            Err(PositionExportError::InvalidQuantity { .. })
+        ));
+        assert_eq!(
+            Position::try_from(position(Rational64::from_integer(5)))
+                .unwrap()
+                .quantity,
+            5
+        );
+    }
+
+    #[test]
+    fn test_position_days_open() {
+        let position = |days_open: &str| Position {
+            symbol: "SPY".to_string(),
+            instrument_type: "STOCK".to_string(),
+            quantity: 1,
+            strike_price: Decimal::default(),
+            call_or_put: OptionType::Call,
+            days_open: days_open.to_string(),
+            net_liq: Decimal::default(),
+        };
+
+        assert_eq!(position("3d").days_open(), Some(3));
+        assert_eq!(position("7").days_open(), Some(7));
+        assert_eq!(position("").days_open(), None);
+        assert_eq!(position("d").days_open(), None);
+        assert_eq!(position("é").days_open(), None);
+    }
 
     #[test]
     fn test_trade_expiration_date_csv_from_str() {
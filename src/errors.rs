@@ -1,10 +1,27 @@
+use crate::api::{
+    orders::{OrderType, TimeInForce},
+    transactions::ValueEffect,
+    InstrumentType,
+};
+
+use num_rational::Rational64;
+
 use std::error::Error;
 use std::fmt;
 
+pub(crate) const OTP_LENGTH: usize = 6;
+
 #[derive(Debug)]
 pub enum ApiError {
     Request(RequestError),
-    Decode { e: Box<dyn Error>, url: String },
+    Decode {
+        e: Box<dyn Error>,
+        url: String,
+        path: Option<String>,
+        body: Option<String>,
+    },
+    Order(OrderBuildError),
+    InvalidOtp,
 }
 
 impl fmt::Display for ApiError {
@@ -13,21 +30,91 @@ impl fmt::Display for ApiError {
             Self::Request(e) => {
                 write!(f, "{}", e)
             }
-            Self::Decode { e, url } => {
+            Self::Decode { e, url, .. } => {
                 write!(f, "Error decoding {}. {}", url, e)
             }
+            Self::Order(e) => {
+                write!(f, "{}", e)
+            }
+            Self::InvalidOtp => {
+                write!(
+                    f,
+                    "Invalid one-time password, expected {} digits",
+                    OTP_LENGTH
+                )
+            }
         }
     }
 }
 
 impl Error for ApiError {}
 
+impl ApiError {
+    /// Whether the request may succeed if retried, e.g. after being rate limited.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+
+    /// The path within the response JSON at which decoding failed e.g. `data.items[0].symbol`.
+    pub fn json_path(&self) -> Option<&str> {
+        match self {
+            Self::Decode { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The body of a response that could not be decoded.
+    pub fn raw_body(&self) -> Option<&str> {
+        match self {
+            Self::Decode { body, .. } => body.as_deref(),
+            _ => None,
+        }
+    }
+}
+
 impl From<RequestError> for ApiError {
     fn from(e: RequestError) -> Self {
         ApiError::Request(e)
     }
 }
 
+impl From<OrderBuildError> for ApiError {
+    fn from(e: OrderBuildError) -> Self {
+        ApiError::Order(e)
+    }
+}
+
+/// Returned by [`Session::from_env`](crate::Session::from_env).
+#[derive(Debug)]
+pub enum EnvError {
+    MissingVar { name: &'static str },
+    Api(ApiError),
+}
+
+impl Error for EnvError {}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingVar { name } => {
+                write!(f, "Missing environment variable {}", name)
+            }
+            Self::Api(e) => {
+                write!(f, "{}", e)
+            }
+        }
+    }
+}
+
+impl From<ApiError> for EnvError {
+    fn from(e: ApiError) -> Self {
+        EnvError::Api(e)
+    }
+}
+
 #[derive(Debug)]
 pub enum RequestError {
     FailedRequest {
@@ -39,6 +126,11 @@ pub enum RequestError {
         body: String,
         url: String,
     },
+    /// The session token has expired or is invalid, and a new session is needed.
+    Unauthorized {
+        body: String,
+        url: String,
+    },
     InvalidHeader {
         e: reqwest::header::InvalidHeaderValue,
     },
@@ -50,6 +142,20 @@ impl From<reqwest::header::InvalidHeaderValue> for RequestError {
     }
 }
 
+impl RequestError {
+    /// Whether the request may succeed if retried, i.e. it failed to send, was rate limited or
+    /// failed with a server error.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::FailedRequest { .. } => true,
+            Self::FailedResponse { status, .. } => {
+                *status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            _ => false,
+        }
+    }
+}
+
 impl Error for RequestError {}
 
 impl fmt::Display for RequestError {
@@ -65,9 +171,119 @@ impl fmt::Display for RequestError {
                     status, body, url
                 )
             }
+            Self::Unauthorized { body, url } => {
+                write!(f, "Unauthorized (body: {}) for {}", body, url)
+            }
             Self::InvalidHeader { e } => {
                 write!(f, "Invalid header: {}", e)
             }
         }
     }
 }
+
+#[derive(Debug)]
+pub enum OrderBuildError {
+    NoLegs,
+    MissingPrice,
+    UnsupportedOrderType(OrderType),
+    UnsupportedTimeInForce(TimeInForce),
+    UnsupportedInstrumentType {
+        symbol: String,
+        instrument_type: InstrumentType,
+    },
+    InvalidQuantity {
+        symbol: String,
+    },
+    DuplicateLeg {
+        symbol: String,
+    },
+    PriceEffectMismatch {
+        expected: ValueEffect,
+        actual: ValueEffect,
+    },
+}
+
+impl Error for OrderBuildError {}
+
+impl fmt::Display for OrderBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoLegs => {
+                write!(f, "Order has no legs")
+            }
+            Self::MissingPrice => {
+                write!(f, "Limit order is missing a price")
+            }
+            Self::UnsupportedOrderType(order_type) => {
+                write!(f, "Unsupported order type {:?}", order_type)
+            }
+            Self::UnsupportedTimeInForce(time_in_force) => {
+                write!(f, "Unsupported time in force {:?}", time_in_force)
+            }
+            Self::UnsupportedInstrumentType {
+                symbol,
+                instrument_type,
+            } => {
+                write!(
+                    f,
+                    "Unsupported instrument type {:?} for leg {}",
+                    instrument_type, symbol
+                )
+            }
+            Self::InvalidQuantity { symbol } => {
+                write!(f, "Invalid quantity for leg {}", symbol)
+            }
+            Self::DuplicateLeg { symbol } => {
+                write!(f, "Duplicate leg for {}", symbol)
+            }
+            Self::PriceEffectMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "Price effect {:?} does not match legs (expected {:?})",
+                    actual, expected
+                )
+            }
+        }
+    }
+}
+
+/// Returned when converting an API position into a positions CSV export row.
+#[derive(Debug)]
+pub enum PositionExportError {
+    UnsupportedInstrumentType(InstrumentType),
+    /// Exports only have whole quantities that fit in an `i32`.
+    InvalidQuantity {
+        symbol: String,
+        quantity: Rational64,
+    },
+}
+
+impl Error for PositionExportError {}
+
+impl fmt::Display for PositionExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnsupportedInstrumentType(instrument_type) => {
+                write!(f, "Unsupported instrument type {:?}", instrument_type)
+            }
+            Self::InvalidQuantity { symbol, quantity } => {
+                write!(f, "Invalid quantity {} for {}", quantity, symbol)
+            }
+        }
+    }
+}
+
+/// A string that does not name any variant of `type_name`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseEnumError {
+    pub type_name: &'static str,
+    pub value: String,
+}
+
+impl Error for ParseEnumError {}
+
+impl fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid {}: {}", self.type_name, self.value)
+    }
+}
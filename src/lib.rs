@@ -2,9 +2,8 @@
 //!
 //! ## Example
 //!
-//! ```rust
-//! use tastyworks::Session;
-//! use num_traits::ToPrimitive;
+//! ```rust,no_run
+//! use tastyworks::{common::format_quantity, Session};
 //!
 //! // Requests made by the API are asynchronous, so you must use a runtime such as `tokio`.
 //! #[tokio::main]
@@ -24,48 +23,76 @@
 //!
 //!   println!("Your active positions:");
 //!   for position in &positions {
-//!       let signed_quantity = position.signed_quantity();
-//!
 //!       // Quantities in the API that could potentially be decimal values are stored as
 //!       // `num_rational::Rational64`. To convert these to floats include the `num-traits` crate
-//!       // in your project and use the `ToPrimitive` trait. To convert these to integers no
-//!       // additional crate is required.
+//!       // in your project and use the `ToPrimitive` trait, or use the formatting helpers in
+//!       // `tastyworks::common` for display.
 //!       println!(
 //!           "{:>10} x {}",
-//!           if signed_quantity.is_integer() {
-//!               signed_quantity.to_integer().to_string()
-//!           } else {
-//!               signed_quantity.to_f64().unwrap().to_string()
-//!           },
+//!           format_quantity(position.signed_quantity()),
 //!           position.symbol
 //!       );
 //!   }
 //! }
 //! ```
+//!
+//! ## Cancellation
+//!
+//! Requests are cancelled by dropping their future, e.g. when it loses a `tokio::select!` or
+//! its task is aborted, which closes or returns its connection to the pool. Requests that only
+//! read are cancel safe. Functions that make several requests, such as [`market_metrics()`] or
+//! [`all_positions`], cancel all outstanding requests when dropped, discarding any results
+//! already received.
+//!
+//! [`place_order`], [`replace_order`] and [`close_position`] are not cancel safe: once the
+//! request has been sent, the order may still be placed or replaced after the future is
+//! dropped. Check the account's orders, e.g. with the account streamer, before sending the
+//! order again.
+//!
+//! The streamers are synchronous and block the calling thread, so use
+//! `tokio::task::spawn_blocking` or a dedicated thread from async code. Connecting blocks until
+//! the server responds, [`streamer::Client::add_subscription`] sleeps 200ms for each chunk of
+//! symbols, [`streamer::Client::candles`] blocks until all candles arrive or it times out, and
+//! closing waits for the server to close the connection. Polling only reads messages that have
+//! already arrived, unless the server advises to reconnect.
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeZone, Utc};
 use futures::{stream, StreamExt};
 use itertools::Itertools;
+use reqwest::Method;
 
+#[cfg(feature = "streamer")]
+pub mod account_streamer;
 pub mod api;
+pub mod cache;
 pub mod common;
 pub mod csv;
 pub mod errors;
 pub mod request;
 pub mod session;
+#[cfg(feature = "streamer")]
 pub mod streamer;
 pub mod symbol;
+pub mod transport;
 
-use crate::errors::*;
-pub use crate::{api::*, request::*, session::Session};
+pub use crate::{
+    api::*,
+    request::*,
+    session::{RefreshingSession, Session},
+};
+use crate::{
+    common::{Decimal, ExpirationDate},
+    errors::*,
+};
 
 const MAX_SYMBOL_SUMMARY_BATCH_SIZE: usize = 500;
+const MAX_MARKET_DATA_BATCH_SIZE: usize = 100;
 const PARALLEL_REQUESTS: usize = 10;
 
 pub async fn accounts(session: &Session) -> Result<Vec<accounts::Account>, ApiError> {
     let url = "customers/me/accounts";
     let response: api::Response<accounts::Response> =
-        deserialize_response(request(url, "", session).await?).await?;
+        deserialize_response(request(url, "", session).await?, session).await?;
     Ok(response
         .data
         .items
@@ -77,15 +104,40 @@ pub async fn accounts(session: &Session) -> Result<Vec<accounts::Account>, ApiEr
 pub async fn watchlists(session: &Session) -> Result<Vec<watchlists::Item>, ApiError> {
     let url = "watchlists";
     let response: api::Response<watchlists::Response> =
-        deserialize_response(request(url, "", session).await?).await?;
+        deserialize_response(request(url, "", session).await?, session).await?;
     Ok(response.data.items)
 }
 
 pub async fn public_watchlists(session: &Session) -> Result<Vec<watchlists::Item>, ApiError> {
+    let mut items = vec![];
+    let mut pagination = None;
+    while let Some((mut page_items, page_pagination)) =
+        public_watchlists_page(pagination, session).await?
+    {
+        items.append(&mut page_items);
+        if page_pagination.is_none() {
+            break;
+        }
+        pagination = page_pagination;
+    }
+    Ok(items)
+}
+
+pub async fn public_watchlists_page(
+    prev_pagination: Option<Pagination>,
+    session: &Session,
+) -> Result<Option<(Vec<watchlists::Item>, Option<Pagination>)>, ApiError> {
+    let page_offset = match next_page_offset(prev_pagination) {
+        Some(page_offset) => page_offset,
+        None => return Ok(None),
+    };
+
     let url = "public-watchlists";
+    let parameters = format!("page-offset={}", page_offset);
     let response: api::Response<watchlists::Response> =
-        deserialize_response(request(url, "", session).await?).await?;
-    Ok(response.data.items)
+        deserialize_response(request(url, &parameters, session).await?, session).await?;
+
+    Ok(Some((response.data.items, response.pagination)))
 }
 
 pub async fn balances(
@@ -94,20 +146,100 @@ pub async fn balances(
 ) -> Result<balances::Data, ApiError> {
     let url = format!("accounts/{}/balances", account.account_number);
     let response: api::Response<balances::Data> =
-        deserialize_response(request(&url, "", session).await?).await?;
+        deserialize_response(request(&url, "", session).await?, session).await?;
     Ok(response.data)
 }
 
+/// Balances for each of `accounts`, tagged with the account number.
+pub async fn all_balances(
+    accounts: &[accounts::Account],
+    session: &Session,
+) -> Result<Vec<(String, balances::Data)>, ApiError> {
+    let results = stream::iter(accounts.iter().map(|account| async move {
+        balances(account, session)
+            .await
+            .map(|data| (account.account_number.clone(), data))
+    }))
+    .buffered(PARALLEL_REQUESTS)
+    .collect::<Vec<_>>()
+    .await;
+
+    results.into_iter().collect()
+}
+
 pub async fn positions(
     account: &accounts::Account,
     session: &Session,
+) -> Result<Vec<positions::Item>, ApiError> {
+    fetch_positions(account, "", session).await
+}
+
+/// Like [`positions()`], but also includes positions that were closed today.
+pub async fn positions_with_closed(
+    account: &accounts::Account,
+    session: &Session,
+) -> Result<Vec<positions::Item>, ApiError> {
+    fetch_positions(account, "include-closed=true", session).await
+}
+
+/// Like [`positions()`], but with the [`mark`](positions::Item::mark) of each position and
+/// optionally its [`greeks`](positions::Item::greeks), for a snapshot of the portfolio without
+/// using the streamer.
+pub async fn positions_with_marks(
+    account: &accounts::Account,
+    include_greeks: bool,
+    session: &Session,
+) -> Result<Vec<positions::Item>, ApiError> {
+    let params_string = if include_greeks {
+        "include-marks=true&include-greeks=true"
+    } else {
+        "include-marks=true"
+    };
+    fetch_positions(account, params_string, session).await
+}
+
+/// Like [`positions()`], but only the positions with one of `underlying_symbols` as their
+/// underlying, e.g. `["SPY"]` for both SPY shares and options.
+pub async fn positions_for_underlyings(
+    account: &accounts::Account,
+    underlying_symbols: &[String],
+    session: &Session,
+) -> Result<Vec<positions::Item>, ApiError> {
+    let params_string = underlying_symbols
+        .iter()
+        .map(|symbol| format!("underlying-symbol[]={}", symbol))
+        .join("&");
+    fetch_positions(account, &params_string, session).await
+}
+
+async fn fetch_positions(
+    account: &accounts::Account,
+    params_string: &str,
+    session: &Session,
 ) -> Result<Vec<positions::Item>, ApiError> {
     let url = format!("accounts/{}/positions", account.account_number);
     let response: api::Response<positions::Response> =
-        deserialize_response(request(&url, "", session).await?).await?;
+        deserialize_response(request(&url, params_string, session).await?, session).await?;
     Ok(response.data.items)
 }
 
+/// Positions for each of `accounts`, tagged with the account number.
+pub async fn all_positions(
+    accounts: &[accounts::Account],
+    session: &Session,
+) -> Result<Vec<(String, Vec<positions::Item>)>, ApiError> {
+    let results = stream::iter(accounts.iter().map(|account| async move {
+        positions(account, session)
+            .await
+            .map(|items| (account.account_number.clone(), items))
+    }))
+    .buffered(PARALLEL_REQUESTS)
+    .collect::<Vec<_>>()
+    .await;
+
+    results.into_iter().collect()
+}
+
 pub async fn transactions<Tz: TimeZone>(
     account: &accounts::Account,
     start_date: DateTime<Tz>,
@@ -115,59 +247,183 @@ pub async fn transactions<Tz: TimeZone>(
     prev_pagination: Option<Pagination>,
     session: &Session,
 ) -> Result<Option<(Vec<transactions::Item>, Option<Pagination>)>, ApiError> {
-    let page_offset = if let Some(api::Pagination {
-        page_offset,
-        total_pages,
-        ..
-    }) = prev_pagination
-    {
-        if page_offset + 1 >= total_pages {
-            return Ok(None);
-        }
-        page_offset + 1
-    } else {
-        0
+    let range = date_range_parameters(start_date, end_date);
+    fetch_transactions(account, &range, None, prev_pagination, session).await
+}
+
+/// Like [`transactions()`], but sorted by execution time in the given direction, e.g.
+/// [`Desc`](transactions::SortDirection::Desc) to fetch the most recent first.
+pub async fn transactions_sorted<Tz: TimeZone>(
+    account: &accounts::Account,
+    start_date: DateTime<Tz>,
+    end_date: DateTime<Tz>,
+    sort: transactions::SortDirection,
+    prev_pagination: Option<Pagination>,
+    session: &Session,
+) -> Result<Option<(Vec<transactions::Item>, Option<Pagination>)>, ApiError> {
+    let range = date_range_parameters(start_date, end_date);
+    fetch_transactions(account, &range, Some(sort), prev_pagination, session).await
+}
+
+/// Like [`transactions()`], but between precise times with millisecond accuracy rather than
+/// whole days, e.g. to reconcile the fills within a trading session.
+pub async fn transactions_between<Tz: TimeZone>(
+    account: &accounts::Account,
+    start_at: DateTime<Tz>,
+    end_at: DateTime<Tz>,
+    prev_pagination: Option<Pagination>,
+    session: &Session,
+) -> Result<Option<(Vec<transactions::Item>, Option<Pagination>)>, ApiError> {
+    let range = time_range_parameters(start_at, end_at);
+    fetch_transactions(account, &range, None, prev_pagination, session).await
+}
+
+fn date_range_parameters<Tz: TimeZone>(start_date: DateTime<Tz>, end_date: DateTime<Tz>) -> String {
+    format!(
+        "start-date={}&end-date={}",
+        start_date.with_timezone(&Utc),
+        end_date.with_timezone(&Utc)
+    )
+}
+
+fn time_range_parameters<Tz: TimeZone>(start_at: DateTime<Tz>, end_at: DateTime<Tz>) -> String {
+    let format = |time: DateTime<Tz>| {
+        time.with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+    };
+    format!("start-at={}&end-at={}", format(start_at), format(end_at))
+}
+
+async fn fetch_transactions(
+    account: &accounts::Account,
+    range_parameters: &str,
+    sort: Option<transactions::SortDirection>,
+    prev_pagination: Option<Pagination>,
+    session: &Session,
+) -> Result<Option<(Vec<transactions::Item>, Option<Pagination>)>, ApiError> {
+    let page_offset = match next_page_offset(prev_pagination) {
+        Some(page_offset) => page_offset,
+        None => return Ok(None),
     };
 
     let url = format!("accounts/{}/transactions", account.account_number);
-    let parameters = format!(
-        "start-date={}&end-date={}&page-offset={}",
-        start_date.with_timezone(&Utc),
-        end_date.with_timezone(&Utc),
-        page_offset
-    );
+    let mut parameters = format!("{}&page-offset={}", range_parameters, page_offset);
+    if let Some(sort) = sort {
+        parameters.push_str(&format!("&sort={}", sort.parameter()));
+    }
     let response: api::Response<transactions::Response> =
-        deserialize_response(request(&url, &parameters, session).await?).await?;
+        deserialize_response(request(&url, &parameters, session).await?, session).await?;
 
     Ok(Some((response.data.items, response.pagination)))
 }
 
+/// Fetches metrics in batches, several at a time. Batches that fail with a transient error,
+/// such as being rate limited, are retried with backoff. Dropping the returned future cancels
+/// all outstanding batches.
 pub async fn market_metrics(
     symbols: &[String],
     session: &Session,
 ) -> Result<Vec<market_metrics::Item>, ApiError> {
-    let results = stream::iter(symbols.chunks(MAX_SYMBOL_SUMMARY_BATCH_SIZE).map(
-        |batch| async move {
-            let symbols = batch.iter().cloned().join(",");
+    let mut items = vec![];
+    for result in market_metrics_batches(symbols, session).await {
+        items.append(&mut result?);
+    }
+
+    Ok(items)
+}
+
+/// Like [`market_metrics()`], but returns the metrics of the batches that succeeded along with
+/// the errors of those that failed, rather than failing entirely.
+pub async fn market_metrics_partial(
+    symbols: &[String],
+    session: &Session,
+) -> (Vec<market_metrics::Item>, Vec<ApiError>) {
+    let mut items = vec![];
+    let mut errors = vec![];
+    for result in market_metrics_batches(symbols, session).await {
+        match result {
+            Ok(mut batch) => items.append(&mut batch),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    (items, errors)
+}
 
-            let url_path = "market-metrics";
-            let params_string = &format!("symbols={}", symbols);
-            let response: Result<api::Response<market_metrics::Response>, ApiError> =
-                deserialize_response(request(url_path, params_string, session).await?).await;
+/// The earnings of each of `symbols` expected to be reported on or after `from`, e.g. today, in
+/// order of the report date and time of day. Fetched with [`market_metrics()`], which also
+/// returns the last reported earnings of symbols without a date set for their next report.
+pub async fn earnings_calendar(
+    symbols: &[String],
+    from: NaiveDate,
+    session: &Session,
+) -> Result<Vec<(String, market_metrics::Earnings)>, ApiError> {
+    let mut calendar: Vec<_> = market_metrics(symbols, session)
+        .await?
+        .into_iter()
+        .filter_map(|item| Some((item.symbol, item.earnings?)))
+        .filter(|(_, earnings)| matches!(earnings.expected_report_date, Some(date) if date >= from))
+        .collect();
+    calendar.sort_by(|(_, a), (_, b)| a.cmp(b));
+    Ok(calendar)
+}
+
+async fn market_metrics_batches(
+    symbols: &[String],
+    session: &Session,
+) -> Vec<Result<Vec<market_metrics::Item>, ApiError>> {
+    stream::iter(
+        symbols
+            .chunks(MAX_SYMBOL_SUMMARY_BATCH_SIZE)
+            .map(|batch| async move {
+                let params_string = &format!("symbols={}", batch.iter().join(","));
+                with_retries(|| async {
+                    let response: api::Response<market_metrics::Response> = deserialize_response(
+                        request("market-metrics", params_string, session).await?,
+                        session,
+                    )
+                    .await?;
+                    Ok(response.data.items)
+                })
+                .await
+            }),
+    )
+    .buffered(PARALLEL_REQUESTS)
+    .collect()
+    .await
+}
+
+/// Quote snapshots for `symbols`, which must all be of the given instrument type.
+pub async fn market_data(
+    instrument_type: InstrumentType,
+    symbols: &[String],
+    session: &Session,
+) -> Result<Vec<market_data::MarketData>, ApiError> {
+    let parameter = market_data::instrument_type_parameter(instrument_type);
+    let results = stream::iter(symbols.chunks(MAX_MARKET_DATA_BATCH_SIZE).map(|batch| {
+        let parameter = &parameter;
+        async move {
+            let params_string = &format!("{}={}", parameter, batch.iter().join(","));
+            let response: Result<api::Response<market_data::Response>, ApiError> =
+                deserialize_response(
+                    request("market-data/by-type", params_string, session).await?,
+                    session,
+                )
+                .await;
 
             response
-        },
-    ))
+        }
+    }))
     .buffered(PARALLEL_REQUESTS)
     .collect::<Vec<_>>()
     .await;
 
-    let mut json = vec![];
+    let mut items = vec![];
     for result in results.into_iter() {
-        json.append(&mut result?.data.items);
+        items.append(&mut result?.data.items);
     }
 
-    Ok(json)
+    Ok(items)
 }
 
 pub async fn option_chains(
@@ -176,6 +432,366 @@ pub async fn option_chains(
 ) -> Result<Vec<option_chains::Item>, ApiError> {
     let url = format!("option-chains/{}/nested", symbol);
     let response: api::Response<option_chains::Response> =
-        deserialize_response(request(&url, "", session).await?).await?;
+        deserialize_response(request(&url, "", session).await?, session).await?;
     Ok(response.data.items)
 }
+
+/// The sorted expiration dates across all option chains of `symbol`, without the strikes of
+/// the full [`option_chains()`].
+pub async fn option_expirations(
+    symbol: &str,
+    session: &Session,
+) -> Result<Vec<ExpirationDate>, ApiError> {
+    let url = format!("option-chains/{}/nested", symbol);
+    let response: api::Response<option_chains::ExpirationsResponse> =
+        deserialize_response(request(&url, "", session).await?, session).await?;
+    Ok(response
+        .data
+        .items
+        .into_iter()
+        .flat_map(|item| item.expirations)
+        .map(|expiration| expiration.expiration_date)
+        .sorted()
+        .dedup()
+        .collect())
+}
+
+/// The option chains of a futures product, where `product` is the root without a leading
+/// slash e.g. `ES`.
+pub async fn futures_option_chains(
+    product: &str,
+    session: &Session,
+) -> Result<Vec<futures_option_chains::Item>, ApiError> {
+    let url = format!(
+        "futures-option-chains/{}/nested",
+        product.trim_start_matches('/')
+    );
+    let response: api::Response<futures_option_chains::Response> =
+        deserialize_response(request(&url, "", session).await?, session).await?;
+    Ok(response.data.option_chains)
+}
+
+/// Futures products with their contracts, limited to the given product codes e.g. `ES` unless
+/// empty.
+pub async fn futures(
+    product_codes: &[String],
+    session: &Session,
+) -> Result<Vec<instruments::FutureProduct>, ApiError> {
+    let params_string = product_codes
+        .iter()
+        .map(|product_code| format!("product-code[]={}", product_code.trim_start_matches('/')))
+        .join("&");
+    let response: api::Response<instruments::FuturesResponse> = deserialize_response(
+        request("instruments/futures", &params_string, session).await?,
+        session,
+    )
+    .await?;
+    Ok(instruments::group_by_product(response.data.items))
+}
+
+/// The quote symbols of `positions`, looking up the streamer symbols of futures options in
+/// their futures option chains. Futures options that cannot be found are skipped.
+pub async fn positions_quote_symbols(
+    positions: &[positions::Item],
+    session: &Session,
+) -> Result<Vec<String>, ApiError> {
+    let products: Vec<_> = positions
+        .iter()
+        .filter(|position| position.instrument_type == InstrumentType::FutureOption)
+        .filter_map(|position| futures_product(position.underlying_symbol()))
+        .unique()
+        .collect();
+    let results = stream::iter(
+        products
+            .iter()
+            .map(|product| futures_option_chains(product, session)),
+    )
+    .buffered(PARALLEL_REQUESTS)
+    .collect::<Vec<_>>()
+    .await;
+    let mut chains = vec![];
+    for result in results.into_iter() {
+        chains.append(&mut result?);
+    }
+
+    Ok(positions
+        .iter()
+        .filter_map(|position| match position.instrument_type {
+            InstrumentType::FutureOption => chains
+                .iter()
+                .find_map(|chain| chain.streamer_symbol(&position.symbol))
+                .map(String::from),
+            _ => Some(position.quote_symbol()),
+        })
+        .collect())
+}
+
+// the product of a future e.g. ES for /ESZ3, the symbol less its month code and year digit
+fn futures_product(future: &str) -> Option<&str> {
+    let future = future.trim_start_matches('/');
+    future.get(..future.len().checked_sub(2)?)
+}
+
+pub async fn place_order(
+    account: &accounts::Account,
+    order: &orders::NewOrder,
+    session: &Session,
+) -> Result<orders::OrderResponse, ApiError> {
+    let url = format!("accounts/{}/orders", account.account_number);
+    let body = serde_json::to_string(order).unwrap();
+    let response: api::Response<orders::OrderResponse> = deserialize_response(
+        request_with_body(&url, Method::POST, body, session).await?,
+        session,
+    )
+    .await?;
+    Ok(response.data)
+}
+
+/// Atomically replaces the working order `order_id` with `order`, e.g. to change its price.
+pub async fn replace_order(
+    account: &accounts::Account,
+    order_id: u64,
+    order: &orders::NewOrder,
+    session: &Session,
+) -> Result<orders::OrderResponse, ApiError> {
+    let url = format!("accounts/{}/orders/{}", account.account_number, order_id);
+    let body = serde_json::to_string(order).unwrap();
+    let response: api::Response<orders::OrderResponse> = deserialize_response(
+        request_with_body(&url, Method::PUT, body, session).await?,
+        session,
+    )
+    .await?;
+    Ok(response.data)
+}
+
+/// Places an order that closes the whole position. `price` is required for limit orders.
+pub async fn close_position(
+    account: &accounts::Account,
+    position: &positions::Item,
+    order_type: orders::OrderType,
+    price: Option<Decimal>,
+    session: &Session,
+) -> Result<orders::OrderResponse, ApiError> {
+    let opening_action = match position.quantity_direction {
+        positions::QuantityDirection::Long => transactions::TradeAction::BuyToOpen,
+        positions::QuantityDirection::Short => transactions::TradeAction::SellToOpen,
+    };
+    let action = opening_action.opposing_action();
+
+    let builder = orders::OrderBuilder::new().leg(orders::Leg {
+        instrument_type: position.instrument_type,
+        symbol: position.symbol.clone(),
+        quantity: position.quantity,
+        action,
+    });
+    let builder = match order_type {
+        orders::OrderType::Market => builder.market(),
+        orders::OrderType::Limit => builder.limit_price(
+            price.ok_or(OrderBuildError::MissingPrice)?,
+            action.value_effect(),
+        ),
+        _ => return Err(OrderBuildError::UnsupportedOrderType(order_type).into()),
+    };
+
+    place_order(account, &builder.build()?, session).await
+}
+
+fn next_page_offset(prev_pagination: Option<Pagination>) -> Option<i32> {
+    if let Some(api::Pagination {
+        page_offset,
+        total_pages,
+        ..
+    }) = prev_pagination
+    {
+        if page_offset + 1 >= total_pages {
+            return None;
+        }
+        Some(page_offset + 1)
+    } else {
+        Some(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::MockTransport;
+
+    #[test]
+    fn test_time_range_parameters() {
+        let start_at = DateTime::parse_from_rfc3339("2023-06-16T09:30:00.250-04:00").unwrap();
+        let end_at = DateTime::parse_from_rfc3339("2023-06-16T10:00:00-04:00").unwrap();
+        assert_eq!(
+            time_range_parameters(start_at, end_at),
+            "start-at=2023-06-16T13:30:00.250Z&end-at=2023-06-16T14:00:00.000Z"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_market_metrics_retries_when_rate_limited() {
+        let session = Session::from_token("token").with_transport(
+            MockTransport::new()
+                .respond_with_status(
+                    Method::GET,
+                    "market-metrics",
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "",
+                )
+                .respond_with_status(
+                    Method::GET,
+                    "market-metrics",
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "",
+                )
+                .respond(
+                    Method::GET,
+                    "market-metrics",
+                    r#"{"data":{"items":[{"symbol":"SPY"}]}}"#,
+                ),
+        );
+
+        let started_at = tokio::time::Instant::now();
+        let items = market_metrics(&["SPY".to_string()], &session)
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].symbol, "SPY");
+        // backed off for 500ms, then 1s
+        assert_eq!(started_at.elapsed(), std::time::Duration::from_millis(1500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_market_metrics_gives_up_after_retries() {
+        let session =
+            Session::from_token("token").with_transport(MockTransport::new().respond_with_status(
+                Method::GET,
+                "market-metrics",
+                StatusCode::TOO_MANY_REQUESTS,
+                "",
+            ));
+
+        let started_at = tokio::time::Instant::now();
+        let result = market_metrics(&["SPY".to_string()], &session).await;
+        assert!(matches!(result, Err(e) if e.is_transient()));
+        assert_eq!(started_at.elapsed(), std::time::Duration::from_millis(3500));
+    }
+
+    #[tokio::test]
+    async fn test_option_expirations() {
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            Method::GET,
+            "option-chains/XYZ/nested",
+            r#"{"data":{"items":[
+                {"expirations":[{"expiration-date":"2023-06-23","strikes":[]},{"expiration-date":"2023-06-16"}]},
+                {"expirations":[{"expiration-date":"2023-06-16"}]}
+            ]}}"#,
+        ));
+
+        let expirations = option_expirations("XYZ", &session).await.unwrap();
+        assert_eq!(
+            expirations,
+            vec![
+                "2023-06-16".parse::<ExpirationDate>().unwrap(),
+                "2023-06-23".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replace_order() {
+        use num_rational::Rational64;
+        use transactions::{TradeAction, ValueEffect};
+
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            Method::PUT,
+            "accounts/5WT00000/orders/12345",
+            r#"{"data":{"order":{"id":12345,"account-number":"5WT00000","status":"Live",
+                "order-type":"Limit","time-in-force":"Day","price":"1.05","price-effect":"Debit"}}}"#,
+        ));
+        let account: accounts::Account =
+            serde_json::from_str(r#"{"account-number":"5WT00000"}"#).unwrap();
+        let order = orders::OrderBuilder::new()
+            .leg(orders::Leg {
+                instrument_type: InstrumentType::Equity,
+                symbol: "SPY".to_string(),
+                quantity: Rational64::from_integer(1),
+                action: TradeAction::Buy,
+            })
+            .limit_price("1.05".parse().unwrap(), ValueEffect::Debit)
+            .build()
+            .unwrap();
+
+        let response = replace_order(&account, 12345, &order, &session)
+            .await
+            .unwrap();
+        assert_eq!(response.order.id, 12345);
+        assert_eq!(response.order.status, "Live");
+    }
+
+    #[tokio::test]
+    async fn test_positions_with_marks() {
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            Method::GET,
+            "accounts/5WT00000/positions",
+            r#"{"data":{"items":[{"symbol":"SPY   230616P00400000","quantity":3,
+                "quantity-direction":"Short","instrument-type":"Equity Option",
+                "mark":"-645.0","mark-price":"2.15",
+                "greeks":{"delta":"-0.25","gamma":"0.01","theta":"-0.12","vega":"0.3"}}]}}"#,
+        ));
+        let account: accounts::Account =
+            serde_json::from_str(r#"{"account-number":"5WT00000"}"#).unwrap();
+
+        let positions = positions_with_marks(&account, true, &session)
+            .await
+            .unwrap();
+        assert_eq!(
+            positions[0].mark_price,
+            Some("2.15".parse::<Decimal>().unwrap())
+        );
+        let greeks = positions[0].greeks.as_ref().unwrap();
+        assert_eq!(greeks.delta, Some(-0.25));
+        assert_eq!(greeks.rho, None);
+    }
+
+    #[tokio::test]
+    async fn test_market_metrics_partial() {
+        let session = Session::from_token("token").with_transport(MockTransport::new());
+        let symbols = vec!["SPY".to_string()];
+
+        let (items, errors) = market_metrics_partial(&symbols, &session).await;
+        assert!(items.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(!errors[0].is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_earnings_calendar() {
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            Method::GET,
+            "market-metrics",
+            r#"{"data":{"items":[
+                {"symbol":"AAPL","earnings":{"expected-report-date":"2023-08-03","estimated":false,"time-of-day":"AMC"}},
+                {"symbol":"SPY"},
+                {"symbol":"XYZ","earnings":{"estimated":true}},
+                {"symbol":"MSFT","earnings":{"expected-report-date":"2023-07-25","estimated":false,"time-of-day":"AMC"}},
+                {"symbol":"NFLX","earnings":{"expected-report-date":"2023-07-19","estimated":false,"time-of-day":"AMC","actual-eps":"3.29"}}
+            ]}}"#,
+        ));
+        let symbols: Vec<_> = ["AAPL", "SPY", "XYZ", "MSFT", "NFLX"]
+            .iter()
+            .map(|symbol| symbol.to_string())
+            .collect();
+
+        // NFLX already reported
+        let from = NaiveDate::from_ymd_opt(2023, 7, 20).unwrap();
+        let calendar = earnings_calendar(&symbols, from, &session).await.unwrap();
+        let calendar_symbols: Vec<_> = calendar.iter().map(|(symbol, _)| symbol.as_str()).collect();
+        assert_eq!(calendar_symbols, vec!["MSFT", "AAPL"]);
+
+        // reports on the date itself are included
+        let from = NaiveDate::from_ymd_opt(2023, 8, 3).unwrap();
+        let calendar = earnings_calendar(&symbols, from, &session).await.unwrap();
+        assert_eq!(calendar.len(), 1);
+        assert_eq!(calendar[0].0, "AAPL");
+    }
+}
@@ -6,39 +6,79 @@ use crate::{
 use lazy_static::lazy_static;
 use reqwest::{header, Client, Method};
 
-pub use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
 
-pub(crate) const BASE_URL: &str = "https://api.tastyworks.com";
+pub use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+
+pub const BASE_URL: &str = "https://api.tastyworks.com";
+pub const SANDBOX_BASE_URL: &str = "https://api.cert.tastyworks.com";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const MAX_RETRIES: u32 = 3;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
 
 lazy_static! {
-    static ref CLIENT: Client = Client::builder()
-        .user_agent(format!("tasyworks-rs/{}", VERSION))
+    pub(crate) static ref CLIENT: Client = Client::builder()
+        .user_agent(format!("tastyworks-rs/{}", VERSION))
         .build()
         .unwrap();
 }
 
+/// Makes a GET request. Dropping the returned future cancels the request.
 pub async fn request(
     url_path: &str,
     params_string: &str,
     session: &Session,
 ) -> Result<reqwest::Response, RequestError> {
-    let mut api_token_header_value = header::HeaderValue::from_str(&session.token).unwrap();
-    api_token_header_value.set_sensitive(true);
-
     let params_string = if params_string.is_empty() {
         params_string.to_string()
     } else {
         format!("?{}", params_string)
     };
 
-    let url = &format!("{}/{}{}", BASE_URL, url_path, params_string);
-    let response = build_request(&url, Method::GET)
-        .header(header::AUTHORIZATION, api_token_header_value)
-        .send()
-        .await;
+    let url = &format!("{}/{}{}", session.base_url, url_path, params_string);
+    let response = send(build_authorized_request(url, Method::GET, session), session).await;
 
-    map_result(&url, response).await
+    map_result(url, response).await
+}
+
+pub(crate) async fn request_with_body(
+    url_path: &str,
+    method: Method,
+    body: String,
+    session: &Session,
+) -> Result<reqwest::Response, RequestError> {
+    let url = &format!("{}/{}", session.base_url, url_path);
+    let response = send(
+        build_authorized_request(url, method, session).body(body),
+        session,
+    )
+    .await;
+
+    map_result(url, response).await
+}
+
+pub(crate) async fn send(
+    request: reqwest::RequestBuilder,
+    session: &Session,
+) -> Result<reqwest::Response, reqwest::Error> {
+    session.transport.execute(request.build()?).await
+}
+
+fn build_authorized_request(
+    url: &str,
+    method: Method,
+    session: &Session,
+) -> reqwest::RequestBuilder {
+    let mut api_token_header_value = header::HeaderValue::from_str(&session.token).unwrap();
+    api_token_header_value.set_sensitive(true);
+
+    build_request(url, method)
+        .headers(session.headers.clone())
+        .header(header::AUTHORIZATION, api_token_header_value)
 }
 
 pub(crate) fn build_request(url: &str, method: Method) -> reqwest::RequestBuilder {
@@ -53,27 +93,64 @@ pub(crate) async fn map_result(
     result: Result<reqwest::Response, reqwest::Error>,
 ) -> Result<reqwest::Response, RequestError> {
     match result {
-        Err(e) => {
-            return Err(RequestError::FailedRequest {
-                e,
-                url: obfuscate_account_url(url),
-            });
-        }
+        Err(e) => Err(RequestError::FailedRequest {
+            e,
+            url: obfuscate_account_url(url),
+        }),
         Ok(response) => {
             if response.status() == 200 || response.status() == 201 {
                 Ok(response)
+            } else if response.status() == StatusCode::UNAUTHORIZED {
+                Err(RequestError::Unauthorized {
+                    body: response.text().await.unwrap_or_else(|e| e.to_string()),
+                    url: obfuscate_account_url(url),
+                })
             } else {
-                return Err(RequestError::FailedResponse {
+                Err(RequestError::FailedResponse {
                     status: response.status(),
                     body: response.text().await.unwrap_or_else(|e| e.to_string()),
                     url: obfuscate_account_url(url),
-                });
+                })
             }
         }
     }
 }
 
-pub(crate) async fn deserialize_response<T>(response: reqwest::Response) -> Result<T, ApiError>
+/// Retries `f` with exponential backoff while it fails with a transient error.
+pub(crate) async fn with_retries<F, Fut, T>(f: F) -> Result<T, ApiError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, ApiError>>,
+{
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut retries = 0;
+    loop {
+        match f().await {
+            Err(e) if e.is_transient() && retries < MAX_RETRIES => {
+                log::debug!("Retrying in {:?} after error: {}", delay, e);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+pub(crate) async fn deserialize_response<T>(
+    response: reqwest::Response,
+    session: &Session,
+) -> Result<T, ApiError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    decode_response(response, session.retain_decode_error_body).await
+}
+
+pub(crate) async fn decode_response<T>(
+    response: reqwest::Response,
+    retain_body: bool,
+) -> Result<T, ApiError>
 where
     T: serde::de::DeserializeOwned,
 {
@@ -89,11 +166,18 @@ where
     let de = &mut serde_json::Deserializer::from_slice(&bytes);
     let result: Result<T, _> = serde_path_to_error::deserialize(de);
     result.map_err(|e| ApiError::Decode {
+        path: Some(e.path().to_string()),
+        body: if retain_body {
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        } else {
+            None
+        },
         e: Box::new(e),
         url: obfuscate_account_url(&url),
     })
 }
 
+// masks the account number between `accounts/` and the next `/` or query string
 pub(crate) fn obfuscate_account_url(url: impl AsRef<str>) -> String {
     const ACCOUNTS_STR: &str = "accounts/";
 
@@ -105,7 +189,7 @@ pub(crate) fn obfuscate_account_url(url: impl AsRef<str>) -> String {
                 if char_byte_idx < accounts_byte_idx + ACCOUNTS_STR.len() || ending_separator_found
                 {
                     ch
-                } else if ch == '/' {
+                } else if ch == '/' || ch == '?' {
                     ending_separator_found = true;
                     ch
                 } else {
@@ -129,5 +213,17 @@ mod tests {
             obfuscate_account_url("foo/accounts/123AB/bar"),
             "foo/accounts/*****/bar"
         );
+        assert_eq!(
+            obfuscate_account_url("accounts/5WT0001/transactions?start-date=2023-01-01"),
+            "accounts/*******/transactions?start-date=2023-01-01"
+        );
+        assert_eq!(
+            obfuscate_account_url("accounts/5WT0001?page-offset=1"),
+            "accounts/*******?page-offset=1"
+        );
+        assert_eq!(
+            obfuscate_account_url("accounts/5WÜ1/bar"),
+            "accounts/****/bar"
+        );
     }
 }
@@ -2,45 +2,433 @@ use crate::{
     api::{self, *},
     errors::*,
     request::*,
+    transport::{HttpTransport, Transport},
 };
 
+use bytes::Bytes;
 use reqwest::{header, Method};
+use serde::Serialize;
 
-use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use zeroize::Zeroizing;
 
+/// A logged in session. Cheap to clone, and can be shared between tasks and threads.
+#[derive(Clone)]
 pub struct Session {
     pub(crate) token: String,
+    pub(crate) base_url: String,
+    pub(crate) headers: header::HeaderMap,
+    pub(crate) retain_decode_error_body: bool,
+    pub(crate) transport: Arc<dyn Transport>,
 }
 
 impl Session {
     pub fn from_token(token: impl Into<String>) -> Self {
         Self {
             token: token.into(),
+            base_url: BASE_URL.to_string(),
+            headers: header::HeaderMap::new(),
+            retain_decode_error_body: false,
+            transport: Arc::new(HttpTransport),
         }
     }
 
+    /// Sends the requests made with this session using `transport` instead of over HTTP.
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// The API that requests are made to, e.g. [`SANDBOX_BASE_URL`]. Defaults to [`BASE_URL`].
+    /// The quote streamer also connects to the environment of this API.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Headers that are added to every request made with this session, replacing any
+    /// defaults or headers added before of the same name e.g. `User-Agent`.
+    pub fn with_headers(mut self, headers: header::HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Overrides the default `tastyworks-rs/<version>` user agent, e.g. with one that has been
+    /// whitelisted for your app.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Result<Self, RequestError> {
+        self.headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_str(user_agent)?,
+        );
+        Ok(self)
+    }
+
+    /// Keeps the body of responses that fail to decode, available from
+    /// [`ApiError::raw_body`]. Off by default as bodies may contain account details.
+    pub fn with_decode_error_body(mut self, retain: bool) -> Self {
+        self.retain_decode_error_body = retain;
+        self
+    }
+
+    /// Logs in with the given credentials, where `otp` is the 6 digit two-factor code if
+    /// enabled. Returns [`ApiError::InvalidOtp`] without sending a request if it is malformed.
+    /// Use [`Session::log_in`] to send the login request with custom headers.
     pub async fn from_credentials(
         login: impl AsRef<str>,
         password: impl AsRef<str>,
         otp: Option<impl AsRef<str>>,
     ) -> Result<Self, ApiError> {
-        let mut map = HashMap::new();
-        map.insert("login", login.as_ref());
-        map.insert("password", password.as_ref());
-        let json = serde_json::to_string(&map).unwrap();
-        let url = format!("{}/sessions", BASE_URL);
-        let mut request = build_request(&url, Method::POST).body(json);
-        if let Some(otp) = otp {
-            let mut otp_header_value =
-                header::HeaderValue::from_str(otp.as_ref()).map_err(Into::<RequestError>::into)?;
-            otp_header_value.set_sensitive(true);
-            request = request.header("X-Tastyworks-OTP", otp_header_value);
+        Self::default().log_in(login, password, otp).await
+    }
+
+    /// Like [`Session::from_credentials`], but logs in with the base URL and headers of this
+    /// session, e.g. `Session::default().with_user_agent(..)?.log_in(..)`, and returns it with
+    /// the new token.
+    pub async fn log_in(
+        self,
+        login: impl AsRef<str>,
+        password: impl AsRef<str>,
+        otp: Option<impl AsRef<str>>,
+    ) -> Result<Self, ApiError> {
+        let json = login_body(login.as_ref(), password.as_ref());
+        self.log_in_with_body(json, otp).await
+    }
+
+    /// Like [`Session::from_credentials`], but logs in to the sandbox environment.
+    pub async fn from_sandbox_credentials(
+        login: impl AsRef<str>,
+        password: impl AsRef<str>,
+        otp: Option<impl AsRef<str>>,
+    ) -> Result<Self, ApiError> {
+        Self::default()
+            .with_base_url(SANDBOX_BASE_URL)
+            .log_in(login, password, otp)
+            .await
+    }
+
+    /// Like [`Session::from_credentials`], but the password is only requested from `password`
+    /// when building the login request and is zeroized immediately afterwards, as is the
+    /// request body once sent, so it need not be kept around by the caller e.g. when read from
+    /// a password manager. Copies made by the TLS implementation are out of this crate's control.
+    pub async fn from_credentials_secret(
+        login: impl AsRef<str>,
+        password: impl FnOnce() -> String,
+        otp: Option<impl AsRef<str>>,
+    ) -> Result<Self, ApiError> {
+        let json = login_body(login.as_ref(), &Zeroizing::new(password()));
+        Self::default().log_in_with_body(json, otp).await
+    }
+
+    /// Uses the token in `TW_SESSION_TOKEN` if set, otherwise logs in with `TW_LOGIN`,
+    /// `TW_PASSWORD` and the optional 2FA code in `TW_OTP`.
+    pub async fn from_env() -> Result<Self, EnvError> {
+        if let Ok(token) = env::var("TW_SESSION_TOKEN") {
+            return Ok(Self::from_token(token));
         }
-        let request_result = map_result(&url, request.send().await).await?;
-        let response: api::Response<sessions::Response> =
-            deserialize_response(request_result).await?;
-        Ok(Session {
-            token: response.data.session_token,
+
+        let var = |name| env::var(name).map_err(|_| EnvError::MissingVar { name });
+        let login = var("TW_LOGIN")?;
+        let password = var("TW_PASSWORD")?;
+        Self::from_credentials_secret(login, || password, env::var("TW_OTP").ok())
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn log_in_with_body(
+        mut self,
+        json: Zeroizing<Vec<u8>>,
+        otp: Option<impl AsRef<str>>,
+    ) -> Result<Self, ApiError> {
+        let response = login_response(&self, json, otp).await?;
+        self.token = response.session_token;
+        Ok(self)
+    }
+}
+
+/// A session without a token, to be configured with the `with_` methods before
+/// [`Session::log_in`].
+impl Default for Session {
+    fn default() -> Self {
+        Self::from_token(String::new())
+    }
+}
+
+/// A session for long running processes, which logs in again with a remember token when its
+/// session token expires.
+pub struct RefreshingSession {
+    login: String,
+    // remember tokens can only be used once, so refreshes are serialized
+    remember_token: tokio::sync::Mutex<Zeroizing<String>>,
+    session: RwLock<Session>,
+}
+
+impl RefreshingSession {
+    pub async fn from_credentials(
+        login: impl Into<String>,
+        password: impl AsRef<str>,
+        otp: Option<impl AsRef<str>>,
+    ) -> Result<Self, ApiError> {
+        Self::log_in(Session::default(), login, password, otp).await
+    }
+
+    /// Like [`RefreshingSession::from_credentials`], but logs in with the base URL, headers and
+    /// transport of `session`, which are kept when logging in again, e.g. to use the sandbox.
+    pub async fn log_in(
+        mut session: Session,
+        login: impl Into<String>,
+        password: impl AsRef<str>,
+        otp: Option<impl AsRef<str>>,
+    ) -> Result<Self, ApiError> {
+        let login = login.into();
+        let json = remember_password_body(&login, password.as_ref());
+        let response = login_response(&session, json, otp).await?;
+        let remember_token = response.remember_token.ok_or_else(|| ApiError::Decode {
+            e: "Missing remember token".into(),
+            url: format!("{}/sessions", session.base_url),
+            path: Some("data.remember-token".to_string()),
+            body: None,
+        })?;
+        session.token = response.session_token;
+
+        Ok(Self {
+            login,
+            remember_token: tokio::sync::Mutex::new(Zeroizing::new(remember_token)),
+            session: RwLock::new(session),
         })
     }
+
+    /// The current session, which stops working once its token expires.
+    pub fn session(&self) -> Session {
+        self.session.read().unwrap().clone()
+    }
+
+    /// Makes a request with the current session, e.g.
+    /// `session.call(|s| async move { tastyworks::accounts(&s).await })`. If the session token
+    /// has expired, logs in again and retries the request once.
+    pub async fn call<F, Fut, T>(&self, f: F) -> Result<T, ApiError>
+    where
+        F: Fn(Session) -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let session = self.session();
+        match f(session.clone()).await {
+            Err(ApiError::Request(RequestError::Unauthorized { .. })) => {
+                self.refresh(&session.token).await?;
+                f(self.session()).await
+            }
+            result => result,
+        }
+    }
+
+    // logs in again unless another request already did since `expired_token` was used
+    async fn refresh(&self, expired_token: &str) -> Result<(), ApiError> {
+        let mut remember_token = self.remember_token.lock().await;
+        let session = self.session();
+        if session.token != expired_token {
+            return Ok(());
+        }
+
+        log::debug!("Refreshing session");
+        let json = remember_token_body(&self.login, &remember_token);
+        let response = login_response(&session, json, None::<&str>).await?;
+        if let Some(token) = response.remember_token {
+            *remember_token = Zeroizing::new(token);
+        }
+        self.session.write().unwrap().token = response.session_token;
+        Ok(())
+    }
+}
+
+async fn login_response(
+    session: &Session,
+    json: Zeroizing<Vec<u8>>,
+    otp: Option<impl AsRef<str>>,
+) -> Result<sessions::Response, ApiError> {
+    let url = format!("{}/sessions", session.base_url);
+    // the body owns the credentials, which are zeroized when the request is dropped
+    let body = reqwest::Body::from(Bytes::from_owner(json));
+    let mut request = build_request(&url, Method::POST)
+        .headers(session.headers.clone())
+        .body(body);
+    if let Some(otp) = otp {
+        validate_otp(otp.as_ref())?;
+        let mut otp_header_value =
+            header::HeaderValue::from_str(otp.as_ref()).map_err(Into::<RequestError>::into)?;
+        otp_header_value.set_sensitive(true);
+        request = request.header("X-Tastyworks-OTP", otp_header_value);
+    }
+    let request_result = map_result(&url, send(request, session).await).await?;
+    let response: api::Response<sessions::Response> =
+        decode_response(request_result, false).await?;
+    Ok(response.data)
+}
+
+// checked before sending, as the API responds to a malformed code with an opaque error
+fn validate_otp(otp: &str) -> Result<(), ApiError> {
+    if otp.len() == OTP_LENGTH && otp.bytes().all(|b| b.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(ApiError::InvalidOtp)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct LoginBody<'a> {
+    login: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remember_token: Option<&'a str>,
+    #[serde(skip_serializing_if = "is_false")]
+    remember_me: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl LoginBody<'_> {
+    // written into a buffer large enough for any escaping, so that it is never reallocated
+    // leaving a copy of the credentials behind
+    fn to_json(&self) -> Zeroizing<Vec<u8>> {
+        let credentials_len = self.login.len()
+            + self.password.map_or(0, str::len)
+            + self.remember_token.map_or(0, str::len);
+        let mut json = Zeroizing::new(Vec::with_capacity(64 + 6 * credentials_len));
+        serde_json::to_writer(&mut *json, self).expect("Failed to serialize login body");
+        json
+    }
+}
+
+fn login_body(login: &str, password: &str) -> Zeroizing<Vec<u8>> {
+    LoginBody {
+        login,
+        password: Some(password),
+        remember_token: None,
+        remember_me: false,
+    }
+    .to_json()
+}
+
+fn remember_password_body(login: &str, password: &str) -> Zeroizing<Vec<u8>> {
+    LoginBody {
+        login,
+        password: Some(password),
+        remember_token: None,
+        remember_me: true,
+    }
+    .to_json()
+}
+
+fn remember_token_body(login: &str, remember_token: &str) -> Zeroizing<Vec<u8>> {
+    LoginBody {
+        login,
+        password: None,
+        remember_token: Some(remember_token),
+        remember_me: true,
+    }
+    .to_json()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<Session>();
+    }
+
+    #[test]
+    fn test_headers_are_merged() {
+        let mut headers = header::HeaderMap::new();
+        headers.insert("x-app", header::HeaderValue::from_static("app"));
+        let session = Session::default()
+            .with_user_agent("app/1.0")
+            .unwrap()
+            .with_headers(headers);
+
+        assert_eq!(session.headers[header::USER_AGENT], "app/1.0");
+        assert_eq!(session.headers["x-app"], "app");
+    }
+
+    #[test]
+    fn test_login_bodies() {
+        let body =
+            |json: Zeroizing<Vec<u8>>| serde_json::from_slice::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            body(login_body("user", "pass\"word")),
+            serde_json::json!({"login": "user", "password": "pass\"word"})
+        );
+        assert_eq!(
+            body(remember_password_body("user", "password")),
+            serde_json::json!({"login": "user", "password": "password", "remember-me": true})
+        );
+        assert_eq!(
+            body(remember_token_body("user", "abc")),
+            serde_json::json!({"login": "user", "remember-token": "abc", "remember-me": true})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refreshing_session_retries_after_refresh() {
+        use crate::transport::MockTransport;
+
+        let transport = MockTransport::new()
+            .respond(
+                Method::POST,
+                "sessions",
+                r#"{"data":{"session-token":"token1","remember-token":"remember1"}}"#,
+            )
+            .respond(
+                Method::POST,
+                "sessions",
+                r#"{"data":{"session-token":"token2","remember-token":"remember2"}}"#,
+            )
+            .respond_with_status(
+                Method::GET,
+                "customers/me/accounts",
+                reqwest::StatusCode::UNAUTHORIZED,
+                r#"{"error":{"code":"token_invalid"}}"#,
+            )
+            .respond(
+                Method::GET,
+                "customers/me/accounts",
+                r#"{"data":{"items":[{"account":{"account-number":"5WT00000"}}]}}"#,
+            );
+        let session = Session::default()
+            .with_base_url(SANDBOX_BASE_URL)
+            .with_transport(transport);
+
+        let refreshing = RefreshingSession::log_in(session, "user", "password", None::<&str>)
+            .await
+            .unwrap();
+        assert_eq!(refreshing.session().token, "token1");
+
+        let accounts = refreshing
+            .call(|session| async move { crate::accounts(&session).await })
+            .await
+            .unwrap();
+        assert_eq!(accounts[0].account_number, "5WT00000");
+
+        let session = refreshing.session();
+        assert_eq!(session.token, "token2");
+        assert_eq!(session.base_url, SANDBOX_BASE_URL);
+        assert_eq!(
+            *refreshing.remember_token.lock().await,
+            Zeroizing::new("remember2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_otp() {
+        assert!(validate_otp("012345").is_ok());
+        assert!(matches!(validate_otp(" 012345"), Err(ApiError::InvalidOtp)));
+        assert!(matches!(validate_otp("12345"), Err(ApiError::InvalidOtp)));
+        assert!(matches!(validate_otp("12345a"), Err(ApiError::InvalidOtp)));
+    }
 }
@@ -1,22 +1,92 @@
-use crate::{api, request::request, session::Session};
+use crate::{
+    api::{self, market_data::MarketData},
+    common::Decimal,
+    request::request,
+    session::Session,
+    symbol::OptionSymbol,
+};
 
+use chrono::{DateTime, TimeZone, Utc};
 use itertools::Itertools;
 use num_rational::Rational64;
+use num_traits::ToPrimitive;
 use serde::Deserialize;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 
 const MAX_SUBSCRIPTION_SIZE: usize = 500;
+const FEED_CHANNEL: i32 = 1;
+const CANDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+const CANDLE_FIELDS: &[&str] = &[
+    "eventType",
+    "eventSymbol",
+    "eventFlags",
+    "time",
+    "open",
+    "high",
+    "low",
+    "close",
+    "volume",
+];
+// fields subscribed to by `subscribe_chain` for event types without a subscription
+const CHAIN_EVENT_FIELDS: &[(&str, &[&str])] = &[
+    (
+        "Quote",
+        &["eventSymbol", "bidPrice", "askPrice", "bidSize", "askSize"],
+    ),
+    ("Trade", &["eventSymbol", "price", "size", "dayVolume"]),
+    (
+        "Greeks",
+        &[
+            "eventSymbol",
+            "volatility",
+            "delta",
+            "gamma",
+            "theta",
+            "rho",
+            "vega",
+        ],
+    ),
+    (
+        "Summary",
+        &[
+            "eventSymbol",
+            "openInterest",
+            "dayOpenPrice",
+            "prevDayClosePrice",
+        ],
+    ),
+];
+
+// see https://docs.dxfeed.com/dxfeed/api/com/dxfeed/event/IndexedEvent.html
+const EVENT_FLAG_REMOVE: i64 = 0x02;
+const EVENT_FLAG_SNAPSHOT_END: i64 = 0x08;
+const EVENT_FLAG_SNAPSHOT_SNIP: i64 = 0x10;
 
 pub struct Client {
     base_url: String,
     token: String,
-    socket: Option<tungstenite::protocol::WebSocket<tungstenite::client::AutoStream>>,
+    socket: Option<Socket>,
+    authorized: bool,
     feed_channel: Option<i32>,
     subscription_fields: HashMap<String, Vec<String>>,
+    subscribed_symbols: HashMap<String, HashSet<String>>,
+    // channels set up for symbols with other fields, see `add_subscription_with_fields`
+    extra_channels: HashMap<i32, ExtraChannel>,
+    next_channel: i32,
+    aggregator: Option<QuoteAggregator>,
+    // the latest event per event type and symbol
+    latest_events: HashMap<String, HashMap<String, Event>>,
+    // messages read while waiting for subscription responses, to be processed on the next poll
+    pending_messages: Vec<tungstenite::Message>,
+    // data of other event types polled by `candles`, returned by the next poll
+    pending_data: HashMap<String, SubscriptionData>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,12 +97,84 @@ struct Message {
 
 #[derive(Debug, Deserialize)]
 struct ChannelOpenedMessage {
-    #[serde(rename = "type")]
-    message_type: String,
     channel: i32,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FeedConfigMessage {
+    #[serde(default)]
+    channel: i32,
+    event_fields: Option<HashMap<String, Vec<String>>>,
+}
+
+// reconnect advice as sent in e.g. `{"advice":{"reconnect":"retry"}}`
+#[derive(Debug, Deserialize)]
+struct AdviceMessage {
+    advice: Advice,
+}
+
+#[derive(Debug, Deserialize)]
+struct Advice {
+    reconnect: Option<String>,
+}
+
+/// The reconnect advice of a message if it asks the client to reconnect, i.e. `retry` or
+/// `handshake`. Other advice such as `none` is ignored.
+fn reconnect_advice(msg_json: &str) -> Option<String> {
+    serde_json::from_str::<AdviceMessage>(msg_json)
+        .ok()
+        .and_then(|msg| msg.advice.reconnect)
+        .filter(|reconnect| reconnect == "retry" || reconnect == "handshake")
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorMessage {
+    error: String,
+    message: String,
+}
+
+/// The error of an `ERROR` message, e.g. for a rejected subscription.
+fn error_message(msg_json: &str) -> Option<SubscriptionError> {
+    match serde_json::from_str::<Message>(msg_json) {
+        Ok(msg) if msg.message_type == "ERROR" => {
+            let error = serde_json::from_str::<ErrorMessage>(msg_json).ok()?;
+            Some(SubscriptionError(format!(
+                "{}: {}",
+                error.error, error.message
+            )))
+        }
+        _ => None,
+    }
+}
+
+struct ExtraChannel {
+    name: String,
+    fields: Vec<String>,
+    symbols: HashSet<String>,
+}
+
+/// The state of a [`Client`]'s connection, see [`Client::state`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum StreamerState {
+    Disconnected,
+    /// Connected but not yet authorized, or authorization failed.
+    Connected,
+    Authorized,
+    /// Authorized with the feed channel open, after the first subscription.
+    FeedOpen,
+}
+
+/// The server's response to [`Client::add_subscription`].
+#[derive(Debug, Default)]
+pub struct SubscriptionAck {
+    /// The fields the server will send for each event type, if it confirmed the feed setup.
+    pub event_fields: Option<HashMap<String, Vec<String>>>,
+}
+
 impl Client {
+    /// Requests a quote token for the session's environment, so a sandbox session streams
+    /// from the sandbox dxlink server.
     pub async fn new(session: &Session) -> Result<Self, Box<dyn Error>> {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "kebab-case")]
@@ -51,8 +193,16 @@ impl Client {
             base_url,
             token,
             socket: None,
+            authorized: false,
             feed_channel: None,
             subscription_fields: HashMap::new(),
+            subscribed_symbols: HashMap::new(),
+            extra_channels: HashMap::new(),
+            next_channel: FEED_CHANNEL + 1,
+            aggregator: None,
+            latest_events: HashMap::new(),
+            pending_messages: vec![],
+            pending_data: HashMap::new(),
         })
     }
 
@@ -62,6 +212,7 @@ impl Client {
         log::debug!("Connected to dxfeed: {}", response.status());
 
         self.socket = Some(socket);
+        self.authorized = false;
         self.send_message(
             r#"
 {
@@ -80,7 +231,7 @@ impl Client {
             _ => return Err(ResponseParseError("SETUP".to_string()).into()),
         };
         // flush remaining messages e.g. unauthorized auth message
-        while let Some(_) = self.read_message(false)? {}
+        while self.read_message(false)?.is_some() {}
 
         self.send_message(&format!(
             r#"
@@ -105,6 +256,81 @@ impl Client {
         if auth_response.state != "AUTHORIZED" {
             return Err(NotAuthorizedError.into());
         }
+        self.authorized = true;
+        Ok(())
+    }
+
+    /// How far the connection has been set up, e.g. to show its status.
+    pub fn state(&self) -> StreamerState {
+        match (&self.socket, self.authorized, self.feed_channel) {
+            (None, _, _) => StreamerState::Disconnected,
+            (Some(_), false, _) => StreamerState::Connected,
+            (Some(_), true, None) => StreamerState::Authorized,
+            (Some(_), true, Some(_)) => StreamerState::FeedOpen,
+        }
+    }
+
+    /// Closes the connection cleanly, which should be done before dropping the client as
+    /// servers may throttle clients that disconnect abruptly. Subscriptions are restored by
+    /// [`reconnect`](Client::reconnect).
+    pub fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.authorized = false;
+        self.feed_channel = None;
+        self.pending_messages.clear();
+        self.pending_data.clear();
+        match self.socket.take() {
+            Some(mut socket) => close_socket(&mut socket),
+            None => Ok(()),
+        }
+    }
+
+    /// Reconnects and restores the current subscriptions. Called by
+    /// [`poll_subscriptions`](Client::poll_subscriptions) when the server advises to retry or
+    /// handshake again, which both require a new setup of the connection.
+    pub fn reconnect(&mut self) -> Result<(), Box<dyn Error>> {
+        let subscriptions: Vec<(String, Vec<String>, Vec<String>)> = self
+            .subscribed_symbols
+            .iter()
+            .filter_map(|(name, symbols)| {
+                let symbols: Vec<String> = symbols
+                    .iter()
+                    .filter(|symbol| {
+                        !self
+                            .extra_channels
+                            .values()
+                            .any(|extra| extra.name == *name && extra.symbols.contains(*symbol))
+                    })
+                    .cloned()
+                    .collect();
+                self.subscription_fields
+                    .get(name)
+                    .filter(|_| !symbols.is_empty())
+                    .map(|fields| (name.clone(), fields.clone(), symbols))
+            })
+            .collect();
+        let extra_channels = std::mem::take(&mut self.extra_channels);
+
+        self.socket = None;
+        self.authorized = false;
+        self.feed_channel = None;
+        self.next_channel = FEED_CHANNEL + 1;
+        self.subscription_fields.clear();
+        self.subscribed_symbols.clear();
+        self.pending_messages.clear();
+
+        self.connect()?;
+        for (name, fields, symbols) in subscriptions {
+            self.add_subscription(&name, &fields, &symbols)?;
+        }
+        for extra in extra_channels.into_values() {
+            let fields = extra.fields;
+            let symbol_fields = extra
+                .symbols
+                .into_iter()
+                .map(|symbol| (symbol, fields.clone()))
+                .collect();
+            self.add_subscription_with_fields(&extra.name, &symbol_fields)?;
+        }
         Ok(())
     }
 
@@ -113,36 +339,248 @@ impl Client {
         name: &str,
         fields: &[String],
         symbols: &[String],
+    ) -> Result<SubscriptionAck, Box<dyn Error>> {
+        self.subscribe(name, fields, symbols, None)
+    }
+
+    /// Subscribes to `event_types` e.g. `Quote` and `Greeks` for every call and put of `chains`,
+    /// as fetched with [`option_chains`](crate::option_chains), returning the quote symbols.
+    /// Event types that are already subscribed to keep their fields, otherwise `Quote`, `Trade`,
+    /// `Greeks` and `Summary` have a default set of fields and other event types are an error.
+    /// Blocks like [`add_subscription`](Client::add_subscription), for each event type.
+    pub fn subscribe_chain(
+        &mut self,
+        chains: &[api::option_chains::Item],
+        event_types: &[&str],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let fields = event_types
+            .iter()
+            .map(|name| self.chain_event_fields(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let symbols: Vec<String> = chains
+            .iter()
+            .flat_map(option_chain_symbols)
+            .flatten()
+            .unique()
+            .collect();
+
+        for (name, fields) in event_types.iter().zip(fields) {
+            self.add_subscription(name, &fields, &symbols)?;
+        }
+        Ok(symbols)
+    }
+
+    fn chain_event_fields(&self, name: &str) -> Result<Vec<String>, UnknownEventTypeError> {
+        if let Some(fields) = self.subscription_fields.get(name) {
+            return Ok(fields.clone());
+        }
+
+        CHAIN_EVENT_FIELDS
+            .iter()
+            .find(|(event_type, _)| *event_type == name)
+            .map(|(_, fields)| fields.iter().map(|field| field.to_string()).collect())
+            .ok_or_else(|| UnknownEventTypeError(name.to_string()))
+    }
+
+    /// Subscribes to `name` events with different fields per symbol, given as a map of symbol
+    /// to fields. As dxfeed sets up fields per event type and channel, symbols with fields other
+    /// than those of [`add_subscription`](Client::add_subscription) are subscribed to on
+    /// additional channels, and unsubscribed from the channel of their previous fields if any.
+    /// Polled events are decoded into the union of the fields, with `null`
+    /// values for fields that were not requested for a symbol.
+    pub fn add_subscription_with_fields(
+        &mut self,
+        name: &str,
+        symbol_fields: &HashMap<String, Vec<String>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let groups = symbol_fields
+            .iter()
+            .into_group_map_by(|(_, fields)| (*fields).clone());
+        for (fields, entries) in groups.into_iter().sorted() {
+            let symbols: Vec<String> = entries
+                .into_iter()
+                .map(|(symbol, _)| symbol.clone())
+                .sorted()
+                .collect();
+            let default_fields = self.subscription_fields.get(name);
+            if default_fields.is_none() || default_fields == Some(&fields) {
+                self.leave_other_channels(name, &symbols, FEED_CHANNEL)?;
+                self.subscribe(name, &fields, &symbols, None)?;
+                continue;
+            }
+
+            let existing_channel = self
+                .extra_channels
+                .iter()
+                .find(|(_, extra)| extra.name == name && extra.fields == fields)
+                .map(|(channel, _)| *channel);
+            let channel = match existing_channel {
+                Some(channel) => channel,
+                None => {
+                    let channel = self.next_channel;
+                    self.next_channel += 1;
+                    self.open_channel(channel)?;
+                    self.feed_setup(channel, name, &fields)?;
+                    self.extra_channels.insert(
+                        channel,
+                        ExtraChannel {
+                            name: name.to_string(),
+                            fields,
+                            symbols: HashSet::new(),
+                        },
+                    );
+                    channel
+                }
+            };
+            self.leave_other_channels(name, &symbols, channel)?;
+            self.feed_subscription(channel, name, &symbols, None)?;
+            if let Some(extra) = self.extra_channels.get_mut(&channel) {
+                extra.symbols.extend(symbols.iter().cloned());
+            }
+            self.subscribed_symbols
+                .entry(name.to_string())
+                .or_default()
+                .extend(symbols);
+            self.read_feed_config()?;
+        }
+
+        Ok(())
+    }
+
+    // unsubscribes symbols moving to `channel` from the channels they were subscribed on, so
+    // that their events are not streamed twice
+    fn leave_other_channels(
+        &mut self,
+        name: &str,
+        symbols: &[String],
+        channel: i32,
     ) -> Result<(), Box<dyn Error>> {
+        for (other_channel, symbols) in self.symbols_on_other_channels(name, symbols, channel) {
+            self.feed_unsubscription(other_channel, name, &symbols)?;
+            if let Some(extra) = self.extra_channels.get_mut(&other_channel) {
+                for symbol in &symbols {
+                    extra.symbols.remove(symbol);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The channels other than `channel` that any of `symbols` are subscribed to for `name`
+    /// events on, with those symbols.
+    fn symbols_on_other_channels(
+        &self,
+        name: &str,
+        symbols: &[String],
+        channel: i32,
+    ) -> Vec<(i32, Vec<String>)> {
+        let subscribed = match self.subscribed_symbols.get(name) {
+            Some(subscribed) => subscribed,
+            None => return vec![],
+        };
+        let extra_channel = |symbol: &String| {
+            self.extra_channels
+                .iter()
+                .find(|(_, extra)| extra.name == name && extra.symbols.contains(symbol))
+                .map(|(extra_channel, _)| *extra_channel)
+        };
+        symbols
+            .iter()
+            .filter(|symbol| subscribed.contains(*symbol))
+            .filter_map(|symbol| match extra_channel(symbol) {
+                Some(extra_channel) => Some((extra_channel, symbol.clone())),
+                None => self
+                    .feed_channel
+                    .map(|feed_channel| (feed_channel, symbol.clone())),
+            })
+            .filter(|(other_channel, _)| *other_channel != channel)
+            .into_group_map()
+            .into_iter()
+            .sorted()
+            .collect()
+    }
+
+    fn subscribe(
+        &mut self,
+        name: &str,
+        fields: &[String],
+        symbols: &[String],
+        from_time: Option<i64>,
+    ) -> Result<SubscriptionAck, Box<dyn Error>> {
         if self.socket.is_none() {
             return Err(NotConnectedError.into());
         }
 
         if self.feed_channel.is_none() {
-            self.send_message(
-                r#"
-{
+            self.open_channel(FEED_CHANNEL)?;
+            self.feed_channel = Some(FEED_CHANNEL);
+        }
+        let channel = self.feed_channel.unwrap();
+
+        if !self.subscription_fields.contains_key(name) {
+            self.feed_setup(channel, name, fields)?;
+            self.subscription_fields
+                .insert(name.to_string(), fields.to_vec());
+        }
+
+        self.feed_subscription(channel, name, symbols, from_time)?;
+        self.subscribed_symbols
+            .entry(name.to_string())
+            .or_default()
+            .extend(symbols.iter().cloned());
+
+        self.read_feed_config()
+    }
+
+    fn open_channel(&mut self, channel: i32) -> Result<(), Box<dyn Error>> {
+        self.send_message(&format!(
+            r#"
+{{
   "type": "CHANNEL_REQUEST",
-  "channel": 1,
+  "channel": {channel},
   "service": "FEED",
-  "parameters": {
+  "parameters": {{
     "contract": "AUTO"
-  }
-}
+  }}
+}}
 "#,
-            )?;
+            channel = channel,
+        ))?;
+
+        loop {
             let msg = self.read_message(true)?.ok_or(ReadMessageError)?;
             let msg_json = msg.to_text()?;
-            let response = match serde_json::from_str::<ChannelOpenedMessage>(msg_json) {
-                Ok(response) if response.message_type == "CHANNEL_OPENED" => response,
-                _ => return Err(ResponseParseError("CHANNEL_OPENED".to_string()).into()),
-            };
-            self.feed_channel = Some(response.channel);
+            match serde_json::from_str::<Message>(msg_json) {
+                Ok(response) if response.message_type == "CHANNEL_OPENED" => {
+                    let opened = serde_json::from_str::<ChannelOpenedMessage>(msg_json)
+                        .or(Err(ResponseParseError("CHANNEL_OPENED".to_string())))?;
+                    if opened.channel == channel {
+                        return Ok(());
+                    }
+                }
+                Ok(response) if response.message_type == "ERROR" => {
+                    let error = serde_json::from_str::<ErrorMessage>(msg_json)
+                        .or(Err(ResponseParseError("ERROR".to_string())))?;
+                    return Err(
+                        SubscriptionError(format!("{}: {}", error.error, error.message)).into(),
+                    );
+                }
+                // keep feed data of other channels, keepalives and any other messages for the
+                // next poll, which skips those that are not feed data or reconnect advice
+                _ => self.pending_messages.push(msg),
+            }
         }
+    }
 
-        if !self.subscription_fields.contains_key(name) {
-            self.send_message(&format!(
-                r#"
+    fn feed_setup(
+        &mut self,
+        channel: i32,
+        name: &str,
+        fields: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        self.send_message(&format!(
+            r#"
 {{
   "type": "FEED_SETUP",
   "channel": {channel},
@@ -153,14 +591,19 @@ impl Client {
   }}
 }}
 "#,
-                channel = self.feed_channel.unwrap(),
-                name = name,
-                fields = fields.join("\",\"")
-            ))?;
-            self.subscription_fields
-                .insert(name.to_string(), fields.to_vec());
-        }
+            channel = channel,
+            name = name,
+            fields = fields.join("\",\"")
+        ))
+    }
 
+    fn feed_subscription(
+        &mut self,
+        channel: i32,
+        name: &str,
+        symbols: &[String],
+        from_time: Option<i64>,
+    ) -> Result<(), Box<dyn Error>> {
         for chunk in symbols.chunks(MAX_SUBSCRIPTION_SIZE) {
             self.send_message(&format!(
                 r#"
@@ -170,62 +613,380 @@ impl Client {
   "add": [{add}]
 }}
 "#,
-                channel = self.feed_channel.unwrap(),
+                channel = channel,
                 add = chunk
                     .iter()
-                    .map(|s| format!(r#"{{"type":"{}","symbol":"{}"}}"#, name, s))
+                    .map(|s| format!(
+                        r#"{{"type":"{}","symbol":"{}"{}}}"#,
+                        name,
+                        s,
+                        from_time
+                            .map(|t| format!(r#","fromTime":{}"#, t))
+                            .unwrap_or_default()
+                    ))
                     .join(",")
             ))?;
             // TODO: replace with something more reliable
             std::thread::sleep(std::time::Duration::from_millis(200));
         }
+        Ok(())
+    }
 
+    fn feed_unsubscription(
+        &mut self,
+        channel: i32,
+        name: &str,
+        symbols: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        for chunk in symbols.chunks(MAX_SUBSCRIPTION_SIZE) {
+            self.send_message(&format!(
+                r#"
+{{
+  "type": "FEED_SUBSCRIPTION",
+  "channel": {channel},
+  "remove": [{remove}]
+}}
+"#,
+                channel = channel,
+                remove = chunk
+                    .iter()
+                    .map(|s| format!(r#"{{"type":"{}","symbol":"{}"}}"#, name, s))
+                    .join(",")
+            ))?;
+        }
         Ok(())
     }
 
+    fn read_feed_config(&mut self) -> Result<SubscriptionAck, Box<dyn Error>> {
+        let mut ack = SubscriptionAck::default();
+        while let Some(msg) = self.read_message(false)? {
+            let msg_json = msg.to_text()?;
+            match serde_json::from_str::<Message>(msg_json) {
+                Ok(response) if response.message_type == "FEED_CONFIG" => {
+                    let config = serde_json::from_str::<FeedConfigMessage>(msg_json)
+                        .or(Err(ResponseParseError("FEED_CONFIG".to_string())))?;
+                    // the server may not send every requested field, so decode using its fields
+                    if let Some(event_fields) = &config.event_fields {
+                        for (name, fields) in event_fields {
+                            match self.extra_channels.get_mut(&config.channel) {
+                                Some(extra) if extra.name == *name => extra.fields = fields.clone(),
+                                Some(_) => {}
+                                None => {
+                                    self.subscription_fields
+                                        .insert(name.clone(), fields.clone());
+                                }
+                            }
+                        }
+                    }
+                    ack.event_fields = config.event_fields;
+                }
+                Ok(response) if response.message_type == "ERROR" => {
+                    let error = serde_json::from_str::<ErrorMessage>(msg_json)
+                        .or(Err(ResponseParseError("ERROR".to_string())))?;
+                    return Err(
+                        SubscriptionError(format!("{}: {}", error.error, error.message)).into(),
+                    );
+                }
+                _ => self.pending_messages.push(msg),
+            }
+        }
+
+        Ok(ack)
+    }
+
+    /// The fields polled events of `name` are decoded into, which are the fields of
+    /// [`add_subscription`](Client::add_subscription) followed by any other fields of
+    /// [`add_subscription_with_fields`](Client::add_subscription_with_fields).
+    fn event_fields(&self, name: &str) -> Vec<String> {
+        self.subscription_fields
+            .get(name)
+            .into_iter()
+            .flatten()
+            .chain(
+                self.extra_channels
+                    .values()
+                    .filter(|extra| extra.name == name)
+                    .flat_map(|extra| &extra.fields),
+            )
+            .unique()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the data received since the last poll, or an error if the server rejected a
+    /// subscription since, which may be after [`add_subscription`](Client::add_subscription)
+    /// returned.
     pub fn poll_subscriptions(
         &mut self,
+    ) -> Result<HashMap<String, SubscriptionData>, Box<dyn Error>> {
+        let mut subscription_data = self.read_subscription_data()?;
+        for (name, pending) in self.pending_data.drain() {
+            let data = match subscription_data.remove(&name) {
+                Some(data) => concat_data(pending, data),
+                None => pending,
+            };
+            subscription_data.insert(name, data);
+        }
+        Ok(subscription_data)
+    }
+
+    fn read_subscription_data(
+        &mut self,
     ) -> Result<HashMap<String, SubscriptionData>, Box<dyn Error>> {
         if self.socket.is_none() {
             return Err(NotConnectedError.into());
         }
 
-        let mut new_subscription_data = HashMap::new();
+        let mut messages = std::mem::take(&mut self.pending_messages);
         while let Some(msg) = self.read_message(false)? {
+            messages.push(msg);
+        }
+
+        let mut new_subscription_data = HashMap::new();
+        let reconnect = self.decode_messages(&messages, &mut new_subscription_data)?;
+
+        if let Some(advice) = reconnect {
+            log::info!("Reconnecting to dxfeed as advised: {}", advice);
+            self.reconnect()?;
+        }
+        self.keep_alive()?;
+
+        if let Some(aggregator) = self.aggregator.as_mut() {
+            aggregator.update(&new_subscription_data);
+        }
+        for (name, data) in &new_subscription_data {
+            let latest_events = self.latest_events.entry(name.clone()).or_default();
+            for (symbol, event) in data.events() {
+                latest_events.insert(symbol.to_string(), event);
+            }
+        }
+
+        Ok(new_subscription_data)
+    }
+
+    /// Decodes the feed data of `messages` into `subscription_data`, skipping frames that cannot
+    /// be decoded reliably, and returns the reconnect advice of the last message with any.
+    /// Fails on the first `ERROR` message.
+    fn decode_messages(
+        &self,
+        messages: &[tungstenite::Message],
+        subscription_data: &mut HashMap<String, SubscriptionData>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let mut reconnect = None;
+        for msg in messages {
             let msg_json = msg.to_text()?;
-            let mut feed_data = if let Ok(data) = serde_json::from_str::<DxFeedData>(msg_json) {
-                data
-            } else {
+            if let Some(advice) = reconnect_advice(msg_json) {
+                reconnect = Some(advice);
                 continue;
+            }
+            if let Some(error) = error_message(msg_json) {
+                return Err(error.into());
+            }
+            if let Ok(feed_data) = serde_json::from_str::<DxFeedData>(msg_json) {
+                match self.decode_feed_data(feed_data, subscription_data) {
+                    Err(e) if e.is::<MisalignedDataError>() => {
+                        log::warn!("Skipping feed data: {}", e)
+                    }
+                    result => result?,
+                }
+            }
+        }
+        Ok(reconnect)
+    }
+
+    fn decode_feed_data(
+        &self,
+        mut feed_data: DxFeedData,
+        subscription_data: &mut HashMap<String, SubscriptionData>,
+    ) -> Result<(), Box<dyn Error>> {
+        let name = feed_data
+            .data
+            .first()
+            .and_then(|name| name.as_str())
+            .map(String::from)
+            .ok_or_else(|| ResponseParseError("name".to_string()))?;
+        let data_seq = feed_data
+            .data
+            .get_mut(1)
+            .and_then(|seq| seq.as_array_mut())
+            .ok_or_else(|| ResponseParseError("data seq".to_string()))?;
+        let subscription_fields = match self.extra_channels.get(&feed_data.channel) {
+            Some(extra) if extra.name == name => Some(&extra.fields),
+            _ => self.subscription_fields.get(&name),
+        }
+        .ok_or_else(|| ResponseParseError("missing subscription fields".to_string()))?;
+        // appending a partial event would misalign the fields of every event after it
+        if data_seq.len() % subscription_fields.len() != 0 {
+            return Err(MisalignedDataError {
+                event_type: name,
+                values: data_seq.len(),
+                fields: subscription_fields.len(),
+            }
+            .into());
+        }
+        let event_fields = self.event_fields(&name);
+        let data = subscription_data.entry(name).or_insert(SubscriptionData {
+            subscription_fields: event_fields,
+            data_seq: vec![],
+        });
+        if *subscription_fields == data.subscription_fields {
+            data.data_seq.append(data_seq);
+        } else {
+            data.data_seq.extend(remap_events(
+                data_seq,
+                subscription_fields,
+                &data.subscription_fields,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fetches the candles for `symbol` between `start` and `end`, where `interval` is a dxfeed
+    /// candle period such as `1d` or `5m`. Blocks until the server has sent all candles. Data
+    /// of other subscriptions received meanwhile is returned by the next
+    /// [`poll_subscriptions`](Client::poll_subscriptions). The candle subscription is removed
+    /// again whether or not fetching succeeds.
+    pub fn candles(
+        &mut self,
+        symbol: &str,
+        interval: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Candle>, Box<dyn Error>> {
+        let candle_symbol = format!("{}{{={}}}", symbol, interval);
+        let fields: Vec<String> = CANDLE_FIELDS.iter().map(|f| f.to_string()).collect();
+        let candles = self
+            .subscribe(
+                "Candle",
+                &fields,
+                std::slice::from_ref(&candle_symbol),
+                Some(start.timestamp_millis()),
+            )
+            .and_then(|_| self.read_candles(&candle_symbol, start, end));
+
+        let unsubscribed = match self.feed_channel {
+            Some(channel) => {
+                self.feed_unsubscription(channel, "Candle", std::slice::from_ref(&candle_symbol))
+            }
+            None => Ok(()),
+        };
+        if let Some(symbols) = self.subscribed_symbols.get_mut("Candle") {
+            symbols.remove(&candle_symbol);
+        }
+
+        let candles = candles?;
+        unsubscribed?;
+        Ok(candles)
+    }
+
+    fn read_candles(
+        &mut self,
+        candle_symbol: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Candle>, Box<dyn Error>> {
+        let mut candles = BTreeMap::new();
+        let started_at = Instant::now();
+        let mut snapshot_complete = false;
+        while !snapshot_complete {
+            if started_at.elapsed() > CANDLE_TIMEOUT {
+                return Err(TimeoutError("candles").into());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+
+            let mut subscription_data = self.poll_subscriptions()?;
+            let candle_data = subscription_data.remove("Candle");
+            self.pending_data = subscription_data;
+            let data = match candle_data {
+                Some(data) => data,
+                None => continue,
             };
-            let name = feed_data
-                .data
-                .get(0)
-                .and_then(|name| name.as_str())
-                .map(String::from)
-                .ok_or_else(|| ResponseParseError("name".to_string()))?;
-            let data_seq = feed_data
-                .data
-                .get_mut(1)
-                .and_then(|seq| seq.as_array_mut())
-                .ok_or_else(|| ResponseParseError("data seq".to_string()))?;
-            let subscription_fields = self
-                .subscription_fields
-                .get(&name)
-                .ok_or_else(|| ResponseParseError("missing subscription fields".to_string()))?;
-            new_subscription_data
-                .entry(name)
-                .or_insert(SubscriptionData {
-                    subscription_fields: subscription_fields.clone(),
-                    data_seq: vec![],
-                })
-                .data_seq
-                .append(data_seq);
+            // candles of other subscriptions are returned by the next poll
+            let symbol_idx = match data.field_index("eventSymbol") {
+                Some(symbol_idx) => symbol_idx,
+                None => {
+                    self.pending_data.insert("Candle".to_string(), data);
+                    return Err(ResponseParseError("eventSymbol".to_string()).into());
+                }
+            };
+            let (data, other_data) =
+                data.partition(|event| event[symbol_idx].as_str() == Some(candle_symbol));
+            if !other_data.data_seq.is_empty() {
+                self.pending_data.insert("Candle".to_string(), other_data);
+            }
+
+            let index = |field: &str| {
+                data.field_index(field)
+                    .ok_or_else(|| ResponseParseError(field.to_string()))
+            };
+            let (flags_idx, time_idx) = (index("eventFlags")?, index("time")?);
+            let (open_idx, high_idx, low_idx, close_idx, volume_idx) = (
+                index("open")?,
+                index("high")?,
+                index("low")?,
+                index("close")?,
+                index("volume")?,
+            );
+
+            for event in data.iter_events() {
+                let flags = event[flags_idx].as_i64().unwrap_or(0);
+                if flags & (EVENT_FLAG_SNAPSHOT_END | EVENT_FLAG_SNAPSHOT_SNIP) != 0 {
+                    snapshot_complete = true;
+                }
+                let time = match event[time_idx]
+                    .as_i64()
+                    .and_then(|t| Utc.timestamp_millis_opt(t).single())
+                {
+                    Some(time) => time,
+                    None => continue,
+                };
+                if flags & EVENT_FLAG_REMOVE != 0 {
+                    candles.remove(&time);
+                } else if time >= start && time <= end {
+                    candles.insert(
+                        time,
+                        Candle {
+                            time,
+                            open: event[open_idx].to_price(),
+                            high: event[high_idx].to_price(),
+                            low: event[low_idx].to_price(),
+                            close: event[close_idx].to_price(),
+                            volume: event[volume_idx].as_f64(),
+                        },
+                    );
+                }
+            }
         }
 
-        self.keep_alive()?;
+        Ok(candles.into_values().collect())
+    }
 
-        Ok(new_subscription_data)
+    /// The latest event of each symbol polled so far for the given event type, e.g. for a
+    /// quote board that only shows the last tick.
+    pub fn latest_by_symbol(&self, event_type: &str) -> HashMap<String, Event> {
+        self.latest_events
+            .get(event_type)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Maintains a [`QuoteAggregator`] over the `Trade` events of every poll from now on.
+    pub fn enable_aggregation(&mut self) {
+        self.aggregator.get_or_insert_with(QuoteAggregator::default);
+    }
+
+    pub fn aggregator(&self) -> Option<&QuoteAggregator> {
+        self.aggregator.as_ref()
+    }
+
+    /// The fields subscribed to for each event type.
+    pub fn active_subscriptions(&self) -> &HashMap<String, Vec<String>> {
+        &self.subscription_fields
+    }
+
+    /// The symbols subscribed to for the given event type, e.g. to avoid subscribing twice.
+    pub fn subscribed_symbols(&self, event_type: &str) -> Option<&HashSet<String>> {
+        self.subscribed_symbols.get(event_type)
     }
 
     fn keep_alive(&mut self) -> Result<(), Box<dyn Error>> {
@@ -257,28 +1018,159 @@ impl Client {
         blocking: bool,
     ) -> Result<Option<tungstenite::Message>, Box<dyn Error>> {
         let socket = self.socket.as_mut().ok_or(NotConnectedError)?;
+        read_socket_message(socket, blocking)
+    }
+}
 
-        // see https://github.com/snapview/tungstenite-rs/issues/103
-        let stream = match socket.get_mut() {
-            tungstenite::stream::Stream::Plain(stream) => stream,
-            tungstenite::stream::Stream::Tls(stream) => stream.get_mut(),
-        };
-        stream.set_nonblocking(!blocking)?;
+pub(crate) type Socket = tungstenite::protocol::WebSocket<tungstenite::client::AutoStream>;
+
+pub(crate) fn read_socket_message(
+    socket: &mut Socket,
+    blocking: bool,
+) -> Result<Option<tungstenite::Message>, Box<dyn Error>> {
+    // see https://github.com/snapview/tungstenite-rs/issues/103
+    #[cfg(feature = "native-tls")]
+    let stream = match socket.get_mut() {
+        tungstenite::stream::Stream::Plain(stream) => stream,
+        tungstenite::stream::Stream::Tls(stream) => stream.get_mut(),
+    };
+    #[cfg(not(feature = "native-tls"))]
+    let stream = socket.get_mut();
+    stream.set_nonblocking(!blocking)?;
+
+    let message = socket.read_message();
+    match message {
+        Ok(msg) => {
+            log::debug!("Received message: {}", msg);
+            Ok(Some(msg))
+        }
+        Err(tungstenite::Error::Io(ref e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Sends a close frame and waits for the server to close the connection, giving up after
+/// [`CLOSE_TIMEOUT`].
+pub(crate) fn close_socket(socket: &mut Socket) -> Result<(), Box<dyn Error>> {
+    socket.close(None)?;
 
-        let message = socket.read_message();
-        match message {
-            Ok(msg) => {
-                log::debug!("Received message: {}", msg);
-                Ok(Some(msg))
+    let started_at = Instant::now();
+    loop {
+        match read_socket_message(socket, false) {
+            Ok(Some(_)) => {}
+            Ok(None) if started_at.elapsed() > CLOSE_TIMEOUT => {
+                return Err(TimeoutError("close").into())
             }
-            Err(tungstenite::Error::Io(ref e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                Ok(None)
+            Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            Err(e) => {
+                return match e.downcast_ref::<tungstenite::Error>() {
+                    Some(tungstenite::Error::ConnectionClosed)
+                    | Some(tungstenite::Error::AlreadyClosed) => Ok(()),
+                    _ => Err(e),
+                }
             }
-            Err(e) => Err(e.into()),
         }
     }
 }
 
+/// The quote symbols of every call and put in `chain`, de-duplicated and split into chunks
+/// that fit within a single subscription request.
+pub fn option_chain_symbols(chain: &api::option_chains::Item) -> Vec<Vec<String>> {
+    chain
+        .expirations
+        .iter()
+        .flat_map(|expiration| &expiration.strikes)
+        .flat_map(|strike| vec![&strike.call, &strike.put])
+        .map(|symbol| OptionSymbol::from(symbol).quote_symbol())
+        .unique()
+        .chunks(MAX_SUBSCRIPTION_SIZE)
+        .into_iter()
+        .map(|chunk| chunk.collect())
+        .collect()
+}
+
+/// Running statistics per symbol over `Trade` events, which must be subscribed to with the
+/// `eventSymbol` and `price` fields, and optionally `size` for the VWAP.
+#[derive(Clone, Debug, Default)]
+pub struct QuoteAggregator {
+    stats: HashMap<String, TradeStats>,
+}
+
+#[derive(Clone, Debug)]
+struct TradeStats {
+    last: Rational64,
+    high: Rational64,
+    low: Rational64,
+    notional: f64,
+    volume: f64,
+}
+
+impl QuoteAggregator {
+    pub fn update(&mut self, data: &HashMap<String, SubscriptionData>) {
+        let trades = match data.get("Trade") {
+            Some(trades) => trades,
+            None => return,
+        };
+        let (symbol_idx, price_idx) = match (
+            trades.field_index("eventSymbol"),
+            trades.field_index("price"),
+        ) {
+            (Some(symbol_idx), Some(price_idx)) => (symbol_idx, price_idx),
+            _ => return,
+        };
+        let size_idx = trades.field_index("size");
+
+        for event in trades.iter_events() {
+            let (symbol, price) = match (event[symbol_idx].as_str(), event[price_idx].to_price()) {
+                (Some(symbol), Some(price)) => (symbol, price),
+                _ => continue,
+            };
+            let size = size_idx
+                .and_then(|size_idx| event[size_idx].as_f64())
+                .filter(|size| size.is_finite())
+                .unwrap_or(0.0);
+
+            let stats = self
+                .stats
+                .entry(symbol.to_string())
+                .or_insert_with(|| TradeStats {
+                    last: price,
+                    high: price,
+                    low: price,
+                    notional: 0.0,
+                    volume: 0.0,
+                });
+            stats.last = price;
+            stats.high = stats.high.max(price);
+            stats.low = stats.low.min(price);
+            stats.notional += price.to_f64().unwrap_or(0.0) * size;
+            stats.volume += size;
+        }
+    }
+
+    pub fn last(&self, symbol: &str) -> Option<Rational64> {
+        self.stats.get(symbol).map(|stats| stats.last)
+    }
+
+    pub fn high(&self, symbol: &str) -> Option<Rational64> {
+        self.stats.get(symbol).map(|stats| stats.high)
+    }
+
+    pub fn low(&self, symbol: &str) -> Option<Rational64> {
+        self.stats.get(symbol).map(|stats| stats.low)
+    }
+
+    /// The volume weighted average price, if any trades with a size have been seen.
+    pub fn vwap(&self, symbol: &str) -> Option<f64> {
+        self.stats
+            .get(symbol)
+            .filter(|stats| stats.volume > 0.0)
+            .map(|stats| stats.notional / stats.volume)
+    }
+}
+
 #[derive(Debug)]
 pub struct SubscriptionData {
     subscription_fields: Vec<String>,
@@ -286,6 +1178,51 @@ pub struct SubscriptionData {
 }
 
 impl SubscriptionData {
+    pub fn field_index(&self, field: &str) -> Option<usize> {
+        self.subscription_fields.iter().position(|f| f == field)
+    }
+
+    /// Iterates over the field values of each event, in subscription field order.
+    pub fn iter_events(&self) -> impl Iterator<Item = &[serde_json::Value]> + '_ {
+        self.data_seq.chunks_exact(self.subscription_fields.len())
+    }
+
+    /// The events of each symbol in the order received, for event types subscribed to with
+    /// the `eventSymbol` field.
+    pub fn events(&self) -> Vec<(&str, Event)> {
+        let symbol_idx = match self.field_index("eventSymbol") {
+            Some(symbol_idx) => symbol_idx,
+            None => return vec![],
+        };
+        let fields = Arc::new(self.subscription_fields.clone());
+        self.iter_events()
+            .filter_map(|values| {
+                values[symbol_idx].as_str().map(|symbol| {
+                    (
+                        symbol,
+                        Event {
+                            fields: fields.clone(),
+                            values: values.to_vec(),
+                        },
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Splits the events into those for which `f` is true and the others, in the same fields.
+    fn partition(self, f: impl Fn(&[serde_json::Value]) -> bool) -> (Self, Self) {
+        let (matching, other): (Vec<_>, Vec<_>) = self
+            .data_seq
+            .chunks_exact(self.subscription_fields.len())
+            .partition(|event| f(event));
+        let data = |events: Vec<&[serde_json::Value]>| SubscriptionData {
+            subscription_fields: self.subscription_fields.clone(),
+            data_seq: events.concat(),
+        };
+        (data(matching), data(other))
+    }
+
     pub fn iter_field(&self, field: &str) -> impl Iterator<Item = &serde_json::Value> + '_ {
         let index = self
             .subscription_fields
@@ -294,11 +1231,50 @@ impl SubscriptionData {
             .unwrap_or_else(|| panic!("Missing index for field: {}", field));
 
         self.data_seq
-            .chunks(self.subscription_fields.len())
+            .chunks_exact(self.subscription_fields.len())
             .map(move |chunk| &chunk[index])
     }
 }
 
+/// The values of a single event.
+#[derive(Clone, Debug)]
+pub struct Event {
+    fields: Arc<Vec<String>>,
+    values: Vec<serde_json::Value>,
+}
+
+impl Event {
+    pub fn get(&self, field: &str) -> Option<&serde_json::Value> {
+        self.fields
+            .iter()
+            .position(|f| f == field)
+            .map(|index| &self.values[index])
+    }
+
+    /// The value of a price field such as `bidPrice`, or `None` if it is missing or `NaN`.
+    pub fn price(&self, field: &str) -> Option<Rational64> {
+        self.get(field).and_then(|value| value.to_price())
+    }
+
+    /// A `Quote` event as the same snapshot that [`market_data`](crate::market_data) returns,
+    /// if it has an `eventSymbol`. Fields that were not subscribed to or are `NaN` are `None`,
+    /// as are those a `Quote` event does not have, such as the last price.
+    pub fn quote(&self) -> Option<MarketData> {
+        let decimal = |field| self.price(field).map(Decimal);
+        Some(MarketData {
+            symbol: self.get("eventSymbol")?.as_str()?.to_string(),
+            bid: decimal("bidPrice"),
+            ask: decimal("askPrice"),
+            bid_size: decimal("bidSize"),
+            ask_size: decimal("askSize"),
+            last: None,
+            mark: None,
+            volume: None,
+            updated_at: None,
+        })
+    }
+}
+
 pub trait SubscriptionValue {
     fn to_price(&self) -> Option<Rational64>;
 }
@@ -313,6 +1289,16 @@ impl SubscriptionValue for serde_json::Value {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct Candle {
+    pub time: DateTime<Utc>,
+    pub open: Option<Rational64>,
+    pub high: Option<Rational64>,
+    pub low: Option<Rational64>,
+    pub close: Option<Rational64>,
+    pub volume: Option<f64>,
+}
+
 #[derive(Debug)]
 pub struct Price {
     pub symbol: String,
@@ -321,11 +1307,54 @@ pub struct Price {
 
 #[derive(Debug, Deserialize)]
 struct DxFeedData {
+    #[serde(default)]
+    channel: i32,
     data: Vec<serde_json::Value>,
 }
 
+/// The events of `earlier` followed by those of `later`, in the fields of `later`.
+fn concat_data(earlier: SubscriptionData, mut later: SubscriptionData) -> SubscriptionData {
+    let mut data_seq = if earlier.subscription_fields == later.subscription_fields {
+        earlier.data_seq
+    } else {
+        remap_events(
+            &earlier.data_seq,
+            &earlier.subscription_fields,
+            &later.subscription_fields,
+        )
+    };
+    data_seq.append(&mut later.data_seq);
+    SubscriptionData {
+        subscription_fields: later.subscription_fields,
+        data_seq,
+    }
+}
+
+/// Reorders the values of each event from `from` fields into `to` fields, with `null` for
+/// fields missing from `from`.
+fn remap_events(
+    data_seq: &[serde_json::Value],
+    from: &[String],
+    to: &[String],
+) -> Vec<serde_json::Value> {
+    let indices: Vec<_> = to
+        .iter()
+        .map(|field| from.iter().position(|f| f == field))
+        .collect();
+    data_seq
+        .chunks_exact(from.len())
+        .flat_map(|event| {
+            indices.iter().map(move |index| {
+                index
+                    .map(|index| event[index].clone())
+                    .unwrap_or(serde_json::Value::Null)
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
-struct NotAuthorizedError;
+pub(crate) struct NotAuthorizedError;
 
 impl Error for NotAuthorizedError {}
 
@@ -336,7 +1365,7 @@ impl fmt::Display for NotAuthorizedError {
 }
 
 #[derive(Debug, Clone)]
-struct NotConnectedError;
+pub(crate) struct NotConnectedError;
 
 impl Error for NotConnectedError {}
 
@@ -347,7 +1376,7 @@ impl fmt::Display for NotConnectedError {
 }
 
 #[derive(Debug, Clone)]
-struct ReadMessageError;
+pub(crate) struct ReadMessageError;
 
 impl Error for ReadMessageError {}
 
@@ -358,7 +1387,7 @@ impl fmt::Display for ReadMessageError {
 }
 
 #[derive(Debug, Clone)]
-struct ResponseParseError(String);
+pub(crate) struct ResponseParseError(pub(crate) String);
 
 impl Error for ResponseParseError {}
 
@@ -367,3 +1396,366 @@ impl fmt::Display for ResponseParseError {
         write!(f, "Response could not be parsed: {}", self.0)
     }
 }
+
+#[derive(Debug, Clone)]
+struct SubscriptionError(String);
+
+impl Error for SubscriptionError {}
+
+impl fmt::Display for SubscriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Subscription failed: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct UnknownEventTypeError(String);
+
+impl Error for UnknownEventTypeError {}
+
+impl fmt::Display for UnknownEventTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No default fields for event type: {}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TimeoutError(&'static str);
+
+impl Error for TimeoutError {}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timed out waiting for {}", self.0)
+    }
+}
+
+/// Logged when the server sends a number of values for an event type that is not a multiple
+/// of its subscribed fields, in which case the events of that message cannot be decoded
+/// reliably and are skipped.
+#[derive(Debug, Clone)]
+pub struct MisalignedDataError {
+    pub event_type: String,
+    pub values: usize,
+    pub fields: usize,
+}
+
+impl Error for MisalignedDataError {}
+
+impl fmt::Display for MisalignedDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Received {} values for {} which has {} fields",
+            self.values, self.event_type, self.fields
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_client() -> Client {
+        use crate::transport::MockTransport;
+
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            reqwest::Method::GET,
+            "api-quote-tokens",
+            r#"{"data":{"dxlink-url":"wss://tasty-openapi-ws.dxfeed.com/realtime","token":"abc"}}"#,
+        ));
+        Client::new(&session).await.unwrap()
+    }
+
+    #[test]
+    fn test_option_chain_symbols() {
+        let strikes: Vec<_> = (1..=300)
+            .map(|strike| {
+                serde_json::json!({
+                    "strike-price": strike.to_string(),
+                    "call": format!("SPY   230616C{:05}000", strike),
+                    "put": format!("SPY   230616P{:05}000", strike),
+                })
+            })
+            .collect();
+        let expiration = serde_json::json!({
+            "expiration-type": "Regular",
+            "expiration-date": "2023-06-16",
+            "days-to-expiration": 30,
+            "settlement-type": "PM",
+            "strikes": strikes,
+        });
+        let chain: api::option_chains::Item = serde_json::from_value(serde_json::json!({
+            "underlying-symbol": "SPY",
+            "root-symbol": "SPY",
+            "option-chain-type": "Standard",
+            "shares-per-contract": 100,
+            "deliverables": [],
+            "expirations": [expiration.clone(), expiration],
+        }))
+        .unwrap();
+
+        let chunks = option_chain_symbols(&chain);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), MAX_SUBSCRIPTION_SIZE);
+        assert_eq!(chunks[1].len(), 100);
+        assert_eq!(chunks[0][0], ".SPY230616C1");
+        assert_eq!(chunks[0][1], ".SPY230616P1");
+    }
+
+    #[tokio::test]
+    async fn test_state_before_connect() {
+        let client = test_client().await;
+        assert_eq!(client.state(), StreamerState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_chain_event_fields() {
+        let mut client = test_client().await;
+
+        assert_eq!(
+            client.chain_event_fields("Greeks").unwrap()[1],
+            "volatility"
+        );
+        assert!(client.chain_event_fields("Candle").is_err());
+        client
+            .subscription_fields
+            .insert("Greeks".to_string(), vec!["eventSymbol".to_string()]);
+        assert_eq!(client.chain_event_fields("Greeks").unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_decode_messages_skips_misaligned_data() {
+        let mut client = test_client().await;
+        client.subscription_fields.insert(
+            "Quote".to_string(),
+            vec!["eventSymbol".to_string(), "bidPrice".to_string()],
+        );
+
+        let messages: Vec<_> = [
+            r#"{"type":"FEED_DATA","channel":1,"data":["Quote",["SPY",400.5]]}"#,
+            r#"{"type":"FEED_DATA","channel":1,"data":["Quote",["QQQ",350.5,"IWM"]]}"#,
+            r#"{"type":"FEED_DATA","channel":1,"data":["Quote",["IWM",180.5]]}"#,
+            r#"{"advice":{"reconnect":"retry"}}"#,
+        ]
+        .iter()
+        .map(|msg| tungstenite::Message::Text(msg.to_string()))
+        .collect();
+
+        let mut data = HashMap::new();
+        let reconnect = client.decode_messages(&messages, &mut data).unwrap();
+        assert_eq!(reconnect, Some("retry".to_string()));
+        let symbols: Vec<_> = data["Quote"].iter_field("eventSymbol").collect();
+        assert_eq!(symbols, vec!["SPY", "IWM"]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_messages_fails_on_error() {
+        let mut client = test_client().await;
+        client.subscription_fields.insert(
+            "Quote".to_string(),
+            vec!["eventSymbol".to_string(), "bidPrice".to_string()],
+        );
+
+        let messages: Vec<_> = [
+            r#"{"type":"FEED_DATA","channel":1,"data":["Quote",["SPY",400.5]]}"#,
+            r#"{"type":"ERROR","channel":1,"error":"INVALID_MESSAGE","message":"Unknown symbol"}"#,
+        ]
+        .iter()
+        .map(|msg| tungstenite::Message::Text(msg.to_string()))
+        .collect();
+
+        let result = client.decode_messages(&messages, &mut HashMap::new());
+        assert!(result.unwrap_err().is::<SubscriptionError>());
+    }
+
+    #[tokio::test]
+    async fn test_symbols_on_other_channels() {
+        let mut client = test_client().await;
+        let symbols = |symbols: &[&str]| symbols.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        client.feed_channel = Some(FEED_CHANNEL);
+        client.subscribed_symbols.insert(
+            "Quote".to_string(),
+            symbols(&["SPY", "QQQ", "IWM"]).into_iter().collect(),
+        );
+        client.extra_channels.insert(
+            3,
+            ExtraChannel {
+                name: "Quote".to_string(),
+                fields: symbols(&["eventSymbol", "bidPrice"]),
+                symbols: symbols(&["IWM"]).into_iter().collect(),
+            },
+        );
+
+        // SPY and QQQ are on the default channel and IWM on channel 3, DIA on none
+        assert_eq!(
+            client.symbols_on_other_channels("Quote", &symbols(&["SPY", "IWM", "DIA"]), 4),
+            vec![(FEED_CHANNEL, symbols(&["SPY"])), (3, symbols(&["IWM"]))]
+        );
+        assert_eq!(
+            client.symbols_on_other_channels("Quote", &symbols(&["SPY", "IWM"]), FEED_CHANNEL),
+            vec![(3, symbols(&["IWM"]))]
+        );
+        assert!(client
+            .symbols_on_other_channels("Trade", &symbols(&["SPY"]), 4)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reconnect_advice() {
+        assert_eq!(
+            reconnect_advice(r#"{"advice":{"reconnect":"retry","interval":0}}"#),
+            Some("retry".to_string())
+        );
+        assert_eq!(
+            reconnect_advice(r#"{"type":"ERROR","advice":{"reconnect":"handshake"}}"#),
+            Some("handshake".to_string())
+        );
+        assert_eq!(reconnect_advice(r#"{"advice":{"reconnect":"none"}}"#), None);
+        assert_eq!(
+            reconnect_advice(r#"{"type":"KEEPALIVE","channel":0}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remap_events() {
+        let fields = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let data_seq: Vec<serde_json::Value> =
+            serde_json::from_str(r#"["Quote", "SPY", 100, "Quote", "QQQ", 200]"#).unwrap();
+
+        let remapped = remap_events(
+            &data_seq,
+            &fields(&["eventType", "eventSymbol", "bidSize"]),
+            &fields(&["eventType", "eventSymbol", "bidPrice", "bidSize"]),
+        );
+        assert_eq!(
+            remapped,
+            serde_json::from_str::<Vec<serde_json::Value>>(
+                r#"["Quote", "SPY", null, 100, "Quote", "QQQ", null, 200]"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_concat_data() {
+        let fields = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let earlier = SubscriptionData {
+            subscription_fields: fields(&["eventSymbol", "bidSize"]),
+            data_seq: serde_json::from_str(r#"["SPY", 100]"#).unwrap(),
+        };
+        let later = SubscriptionData {
+            subscription_fields: fields(&["eventSymbol", "bidPrice", "bidSize"]),
+            data_seq: serde_json::from_str(r#"["QQQ", 350.5, 200]"#).unwrap(),
+        };
+
+        let data = concat_data(earlier, later);
+        assert_eq!(
+            data.subscription_fields,
+            fields(&["eventSymbol", "bidPrice", "bidSize"])
+        );
+        assert_eq!(
+            data.data_seq,
+            serde_json::from_str::<Vec<serde_json::Value>>(
+                r#"["SPY", null, 100, "QQQ", 350.5, 200]"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_subscription_data_partition() {
+        let data = SubscriptionData {
+            subscription_fields: vec!["eventSymbol".to_string(), "close".to_string()],
+            data_seq: serde_json::from_str(
+                r#"["SPY{=1d}", 400.5, "QQQ{=1d}", 350.5, "SPY{=1d}", 401.5]"#,
+            )
+            .unwrap(),
+        };
+
+        let (spy, other) = data.partition(|event| event[0] == "SPY{=1d}");
+        assert_eq!(spy.subscription_fields, other.subscription_fields);
+        assert_eq!(
+            spy.iter_field("close").collect::<Vec<_>>(),
+            vec![400.5, 401.5]
+        );
+        assert_eq!(
+            other.iter_field("eventSymbol").collect::<Vec<_>>(),
+            vec!["QQQ{=1d}"]
+        );
+    }
+
+    #[test]
+    fn test_quote_aggregator() {
+        let data = SubscriptionData {
+            subscription_fields: vec![
+                "eventType".to_string(),
+                "eventSymbol".to_string(),
+                "price".to_string(),
+                "size".to_string(),
+            ],
+            data_seq: serde_json::from_str(
+                r#"["Trade", "SPY", 400.5, 100, "Trade", "SPY", 401.5, 300, "Trade", "QQQ", "NaN", 1]"#,
+            )
+            .unwrap(),
+        };
+        let mut aggregator = QuoteAggregator::default();
+        aggregator.update(&vec![("Trade".to_string(), data)].into_iter().collect());
+
+        assert_eq!(aggregator.last("SPY"), Some(Rational64::new(803, 2)));
+        assert_eq!(aggregator.high("SPY"), Some(Rational64::new(803, 2)));
+        assert_eq!(aggregator.low("SPY"), Some(Rational64::new(801, 2)));
+        assert_eq!(aggregator.vwap("SPY"), Some(401.25));
+        assert_eq!(aggregator.last("QQQ"), None);
+    }
+
+    #[test]
+    fn test_subscription_data_events() {
+        let data = SubscriptionData {
+            subscription_fields: vec![
+                "eventType".to_string(),
+                "eventSymbol".to_string(),
+                "bidPrice".to_string(),
+            ],
+            data_seq: serde_json::from_str(
+                r#"["Quote", "SPY", 400.5, "Quote", "QQQ", "NaN", "Quote", "SPY", 401]"#,
+            )
+            .unwrap(),
+        };
+        let latest: HashMap<_, _> = data.events().into_iter().collect();
+
+        assert_eq!(latest.len(), 2);
+        assert_eq!(
+            latest["SPY"].price("bidPrice"),
+            Some(Rational64::from_integer(401))
+        );
+        assert_eq!(latest["QQQ"].price("bidPrice"), None);
+        assert!(latest["QQQ"].get("askPrice").is_none());
+    }
+
+    #[test]
+    fn test_quote_mid() {
+        let data = SubscriptionData {
+            subscription_fields: vec![
+                "eventSymbol".to_string(),
+                "bidPrice".to_string(),
+                "askPrice".to_string(),
+            ],
+            data_seq: serde_json::from_str(r#"["SPY", 400.5, 401, "QQQ", "NaN", 300]"#).unwrap(),
+        };
+        let quotes: Vec<_> = data
+            .events()
+            .into_iter()
+            .filter_map(|(_, event)| event.quote())
+            .collect();
+
+        assert_eq!(quotes[0].symbol, "SPY");
+        assert_eq!(quotes[0].mid(), Some(Rational64::new(1603, 4)));
+        assert_eq!(quotes[1].mid(), None);
+        assert_eq!(
+            quotes[1].ask.map(|ask| ask.0),
+            Some(Rational64::from_integer(300))
+        );
+    }
+}
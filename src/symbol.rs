@@ -1,11 +1,22 @@
-use crate::common::{ExpirationDate, OptionType};
+use crate::common::{parse_date, ExpirationDate, OptionType};
 
-use chrono::NaiveDate;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
 use num_rational::Rational64;
 
 use std::fmt;
 use std::str::FromStr;
 
+// number of implied decimal places in the OCC strike price field
+const STRIKE_PRICE_DECIMALS: usize = 3;
+
+// weekly option roots and the underlying symbol they belong to
+const WEEKLY_ROOTS: &[(&str, &str)] = &[
+    ("SPXW", "SPX"),
+    ("NDXP", "NDX"),
+    ("RUTW", "RUT"),
+    ("VIXW", "VIX"),
+];
+
 pub struct OptionSymbol<'a>(&'a str);
 
 impl<'a> OptionSymbol<'a> {
@@ -14,9 +25,9 @@ impl<'a> OptionSymbol<'a> {
     }
 
     pub fn quote_symbol(&self) -> String {
-        let price = self.price_component();
-        let integer = price[..5].trim_start_matches('0');
-        let decimal = price[5..].trim_end_matches('0');
+        let (integer, decimal) = self.price_parts();
+        let integer = integer.trim_start_matches('0');
+        let decimal = decimal.trim_end_matches('0');
         format!(
             ".{}{}{}{}{}{}",
             self.underlying_symbol(),
@@ -48,21 +59,55 @@ impl<'a> OptionSymbol<'a> {
         price.unwrap_or_else(|| panic!("Missing price component for symbol: {}", self.0))
     }
 
+    fn price_parts(&self) -> (&str, &str) {
+        let price = self.price_component();
+        let decimal_idx = price
+            .len()
+            .checked_sub(STRIKE_PRICE_DECIMALS)
+            .filter(|_| price.is_ascii())
+            .unwrap_or_else(|| panic!("Invalid price component for symbol: {}", self.0));
+        price.split_at(decimal_idx)
+    }
+
     pub fn expiration_date(&self) -> ExpirationDate {
         let date_str = self.date_component();
-        let date = NaiveDate::parse_from_str(date_str, "%y%m%d")
-            .ok()
-            .map(ExpirationDate);
+        let date = parse_date(date_str).ok().map(ExpirationDate);
         date.unwrap_or_else(|| panic!("Missing expiration date for symbol: {}", self.0))
     }
 
     pub fn underlying_symbol(&self) -> &'a str {
-        let underlying_symbol = self
-            .0
+        strip_weekly(self.root_symbol())
+    }
+
+    /// The option root, which unlike the underlying symbol keeps weekly and adjusted roots
+    /// e.g. SPXW or AAPL1.
+    pub fn root_symbol(&self) -> &'a str {
+        self.0
             .split_whitespace()
             .next()
-            .unwrap_or_else(|| panic!("Missing underlying symbol for symbol: {}", self.0));
-        strip_weekly(underlying_symbol)
+            .unwrap_or_else(|| panic!("Missing underlying symbol for symbol: {}", self.0))
+    }
+
+    /// Whether the option has a weekly root (e.g. SPXW) or otherwise expires on a date other
+    /// than the standard monthly expiration: the third Friday of the month, or the Thursday
+    /// before when that Friday is a market holiday, and for VIX the Wednesday 30 days before
+    /// the third Friday of the following month.
+    pub fn is_weekly(&self) -> bool {
+        let root_symbol = self.root_symbol();
+        if WEEKLY_ROOTS
+            .iter()
+            .any(|(weekly, _)| *weekly == root_symbol)
+        {
+            return true;
+        }
+
+        let date = self.expiration_date().0;
+        let monthly_expiration = if root_symbol == "VIX" {
+            vix_monthly_expiration(date.year(), date.month())
+        } else {
+            monthly_expiration(date.year(), date.month())
+        };
+        date != monthly_expiration
     }
 
     pub fn option_type(&self) -> OptionType {
@@ -77,9 +122,30 @@ impl<'a> OptionSymbol<'a> {
         let price_str = self.price_component();
         let price = i64::from_str(price_str)
             .ok()
-            .map(|i| Rational64::new(i, 1000));
+            .map(|i| Rational64::new(i, 10i64.pow(STRIKE_PRICE_DECIMALS as u32)));
         price.unwrap_or_else(|| panic!("Missing strike price for symbol: {}", self.0))
     }
+
+    pub fn key(&self) -> OptionKey {
+        OptionKey {
+            root_symbol: self.root_symbol().to_string(),
+            expiration_date: self.expiration_date(),
+            option_type: self.option_type(),
+            strike_price: self.strike_price(),
+        }
+    }
+}
+
+/// The identity of an option contract for use as a map key, which unlike
+/// [`Decimal`](crate::common::Decimal) strike prices is `Hash`. Keyed by root rather than
+/// underlying symbol, as e.g. SPX and SPXW options with the same expiration and strike are
+/// different contracts.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OptionKey {
+    pub root_symbol: String,
+    pub expiration_date: ExpirationDate,
+    pub option_type: OptionType,
+    pub strike_price: Rational64,
 }
 
 impl fmt::Display for OptionSymbol<'_> {
@@ -115,14 +181,72 @@ impl fmt::Display for QuoteSymbol<'_> {
     }
 }
 
-pub fn strip_weekly(underlying_symbol: &str) -> &str {
-    if underlying_symbol == "SPXW" {
-        &underlying_symbol[0..3]
+fn third_friday(year: i32, month: u32) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Fri, 3).expect("Invalid month")
+}
+
+// the business day before a third Friday that is a market holiday
+fn previous_business_day(date: NaiveDate) -> NaiveDate {
+    if is_market_holiday(date) {
+        date.pred_opt().expect("Date out of range")
     } else {
-        underlying_symbol
+        date
     }
 }
 
+fn monthly_expiration(year: i32, month: u32) -> NaiveDate {
+    previous_business_day(third_friday(year, month))
+}
+
+fn vix_monthly_expiration(year: i32, month: u32) -> NaiveDate {
+    let (year, month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    let expiration = previous_business_day(third_friday(year, month)) - Duration::days(30);
+    previous_business_day(expiration)
+}
+
+// the market holidays that can fall on the third Friday of a month or the Wednesday 30 days
+// before it, i.e. between the 15th and 21st, or the 13th and 22nd of the previous month
+fn is_market_holiday(date: NaiveDate) -> bool {
+    let juneteenth = date.year() >= 2022
+        && date.month() == 6
+        && match date.weekday() {
+            Weekday::Fri => (18..=19).contains(&date.day()),
+            Weekday::Mon => date.day() == 20,
+            _ => date.day() == 19,
+        };
+    juneteenth || date == easter_sunday(date.year()) - Duration::days(2)
+}
+
+// the anonymous Gregorian algorithm
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("Invalid date of Easter")
+}
+
+pub fn strip_weekly(underlying_symbol: &str) -> &str {
+    WEEKLY_ROOTS
+        .iter()
+        .find(|(weekly, _)| *weekly == underlying_symbol)
+        .map_or(underlying_symbol, |(_, root)| root)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,6 +281,74 @@ mod tests {
         assert_eq!(strike_price, Rational64::new(405, 10));
     }
 
+    #[test]
+    fn test_option_symbol_large_strike_price() {
+        let option_symbol = OptionSymbol::from("SPXW  240119C05000000");
+        assert_eq!(option_symbol.strike_price(), Rational64::from_integer(5000));
+        assert_eq!(option_symbol.quote_symbol(), ".SPX240119C5000");
+
+        let option_symbol = OptionSymbol::from("XYZ 240119C100000500");
+        assert_eq!(option_symbol.strike_price(), Rational64::new(1000005, 10));
+        assert_eq!(option_symbol.quote_symbol(), ".XYZ240119C100000.5");
+    }
+
+    #[test]
+    fn test_option_symbol_is_weekly() {
+        assert!(OptionSymbol::from("SPXW  240119C05000000").is_weekly());
+        assert!(!OptionSymbol::from("SPX   240119C05000000").is_weekly());
+        assert!(OptionSymbol::from("SPY   240126C00480000").is_weekly());
+        assert!(!OptionSymbol::from("IQ 200918P00017500").is_weekly());
+    }
+
+    #[test]
+    fn test_option_symbol_is_weekly_vix() {
+        // the Wednesday 30 days before the third Friday of the following month
+        assert!(!OptionSymbol::from("VIX   240117C00015000").is_weekly());
+        assert!(!OptionSymbol::from("VIX   240918C00015000").is_weekly());
+        assert!(OptionSymbol::from("VIX   240119C00015000").is_weekly());
+        assert!(OptionSymbol::from("VIXW  240117C00015000").is_weekly());
+        // 30 days before Thursday 17 April 2025, as Good Friday was the third Friday
+        assert!(!OptionSymbol::from("VIX   250318C00015000").is_weekly());
+        assert!(OptionSymbol::from("VIX   250319C00015000").is_weekly());
+    }
+
+    #[test]
+    fn test_option_symbol_is_weekly_holiday() {
+        // Good Friday was the third Friday of April 2025 and 2022
+        assert!(!OptionSymbol::from("SPY   250417C00500000").is_weekly());
+        assert!(!OptionSymbol::from("SPX   220414C04500000").is_weekly());
+        assert!(OptionSymbol::from("SPY   240418C00500000").is_weekly());
+        // Juneteenth was the third Friday of June 2026
+        assert!(!OptionSymbol::from("SPY   260618C00500000").is_weekly());
+        assert!(!OptionSymbol::from("SPY   240621C00500000").is_weekly());
+        assert_eq!(
+            easter_sunday(2024),
+            NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_option_symbol_key() {
+        use std::collections::HashMap;
+
+        let mut marks = HashMap::new();
+        marks.insert(OptionSymbol::from("SPXW  230616P04000000").key(), 1);
+        marks.insert(OptionSymbol::from("SPX   230616P04000000").key(), 2);
+
+        assert_eq!(
+            marks.get(&OptionSymbol::from("SPXW  230616P04000000").key()),
+            Some(&1)
+        );
+        assert_eq!(marks.len(), 2);
+    }
+
+    #[test]
+    fn test_strip_weekly() {
+        assert_eq!(strip_weekly("SPXW"), "SPX");
+        assert_eq!(strip_weekly("NDXP"), "NDX");
+        assert_eq!(strip_weekly("SPY"), "SPY");
+    }
+
     #[test]
     fn test_quote_symbol_matches_underlying_symbol() {
         let quote_symbol = QuoteSymbol::from(".IQ200918P17.5");
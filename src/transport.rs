@@ -0,0 +1,133 @@
+use crate::request::CLIENT;
+
+use futures::future::{self, BoxFuture};
+use reqwest::{Method, StatusCode};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Sends the requests made with a [`Session`](crate::Session). The default is [`HttpTransport`],
+/// replace it with [`Session::with_transport`](crate::Session::with_transport) e.g. to use a
+/// [`MockTransport`] in tests.
+pub trait Transport: Send + Sync {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<reqwest::Response, reqwest::Error>>;
+}
+
+/// Sends requests to the tastyworks API over HTTP.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HttpTransport;
+
+impl Transport for HttpTransport {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<reqwest::Response, reqwest::Error>> {
+        Box::pin(CLIENT.execute(request))
+    }
+}
+
+/// Responds to requests with canned JSON, keyed by method and URL path without the base URL
+/// or query e.g. `accounts/5WT00000/positions`. Several responses to the same path are returned
+/// in order, with the last repeated for any further requests. Unknown paths respond with
+/// `404 Not Found`. Clones share the remaining responses.
+#[derive(Clone, Debug, Default)]
+pub struct MockTransport {
+    responses: Arc<Mutex<MockResponses>>,
+}
+
+// the responses yet to be returned per method and path
+type MockResponses = HashMap<(Method, String), VecDeque<(StatusCode, String)>>;
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(self, method: Method, path: impl Into<String>, body: impl Into<String>) -> Self {
+        self.respond_with_status(method, path, StatusCode::OK, body)
+    }
+
+    pub fn respond_with_status(
+        self,
+        method: Method,
+        path: impl Into<String>,
+        status: StatusCode,
+        body: impl Into<String>,
+    ) -> Self {
+        let path = path.into().trim_start_matches('/').to_string();
+        self.responses
+            .lock()
+            .unwrap()
+            .entry((method, path))
+            .or_default()
+            .push_back((status, body.into()));
+        self
+    }
+}
+
+impl Transport for MockTransport {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, Result<reqwest::Response, reqwest::Error>> {
+        let key = (
+            request.method().clone(),
+            request.url().path().trim_start_matches('/').to_string(),
+        );
+        let response = self
+            .responses
+            .lock()
+            .unwrap()
+            .get_mut(&key)
+            .and_then(|responses| {
+                if responses.len() > 1 {
+                    responses.pop_front()
+                } else {
+                    responses.front().cloned()
+                }
+            });
+        let (status, body) = response.unwrap_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("No mock response for {} {}", key.0, key.1),
+            )
+        });
+
+        let response = http::Response::builder()
+            .status(status)
+            .body(body)
+            .expect("Invalid mock response");
+        Box::pin(future::ready(Ok(response.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::RequestError, ApiError, Session};
+
+    #[tokio::test]
+    async fn test_mock_transport() {
+        let session = Session::from_token("token").with_transport(MockTransport::new().respond(
+            Method::GET,
+            "customers/me/accounts",
+            r#"{"data":{"items":[{"account":{"account-number":"5WT00000"}}]}}"#,
+        ));
+
+        let accounts = crate::accounts(&session).await.unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].account_number, "5WT00000");
+
+        let result = crate::watchlists(&session).await;
+        assert!(matches!(
+            result,
+            Err(ApiError::Request(RequestError::FailedResponse {
+                status: StatusCode::NOT_FOUND,
+                ..
+            }))
+        ));
+    }
+}